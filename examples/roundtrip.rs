@@ -0,0 +1,47 @@
+//! Pack an ELF, unpack the result and check that the original ELF is recovered.
+//!
+//! ```sh
+//! cargo run --example roundtrip -- path/to/module.prx
+//! ```
+
+use std::{env, fs, path::PathBuf, process::ExitCode};
+
+use psp_packer::{Error, UnkPspExecutable};
+
+fn main() -> Result<ExitCode, Error> {
+    let Some(path) = env::args_os().nth(1).map(PathBuf::from) else {
+        eprintln!("usage: roundtrip <ELF>");
+        return Ok(ExitCode::FAILURE);
+    };
+
+    let original = fs::read(&path)?;
+    let packed = UnkPspExecutable::from_path(&path)?.compress()?;
+    let unpacked = UnkPspExecutable::from_reader(packed.as_bytes())?.unpack()?;
+    let recovered = unpacked.as_bytes();
+
+    println!(
+        "{}: {} B packed to {} B ({})",
+        path.display(),
+        original.len(),
+        packed.size(),
+        packed.kind()
+    );
+
+    if recovered == original {
+        println!("roundtrip OK");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let differing = original.iter().zip(recovered).filter(|(a, b)| a != b).count();
+    let first = original.iter().zip(recovered).position(|(a, b)| a != b);
+
+    eprintln!("roundtrip FAILED");
+    eprintln!("  original size:  {} B", original.len());
+    eprintln!("  recovered size: {} B", recovered.len());
+    eprintln!("  differing bytes in the common range: {differing}");
+    if let Some(first) = first {
+        eprintln!("  first difference at 0x{first:X}");
+    }
+
+    Ok(ExitCode::FAILURE)
+}
@@ -1,11 +1,17 @@
 use std::path::PathBuf;
 
 use clap::{
-    builder::styling::{AnsiColor, Styles},
+    builder::{
+        styling::{AnsiColor, Styles},
+        PossibleValuesParser,
+    },
     crate_authors, crate_description, crate_name, crate_version, value_parser, Arg, ArgAction,
     Command,
 };
+use clap_complete::Shell;
+use psp_packer::{BssSource, DecryptMode};
 
+use crate::config;
 
 pub(crate) fn create_app() -> Command {
     let styles = Styles::styled()
@@ -14,14 +20,283 @@ pub(crate) fn create_app() -> Command {
         .literal(AnsiColor::Green.on_default())
         .placeholder(AnsiColor::Green.on_default());
 
-    Command::new(crate_name!())
+    let app = Command::new(crate_name!())
         .version(crate_version!())
         .author(crate_authors!())
         .about(crate_description!())
+        .after_long_help(
+            "Default tags, compression level and preset can be set in a `psp-packer.toml` file, \
+             in the current directory or in `$XDG_CONFIG_HOME`:\n\n    tags = [0x457B06F0, \
+             0x8555ABF2]\n    level = 9\n    preset = \"user\"\n\nThe command line options take \
+             precedence over the config file.\n\nWithout `--tags` or `--preset`, the tags can \
+             also be set with the `PSP_PACKER_TAGS` environment variable, as two values (e.g. \
+             `PSP_PACKER_TAGS=\"0x457B06F0 0x8555ABF2\"`). It takes precedence over the config \
+             file.\n\nEverywhere, tags are decimal unless prefixed with `0x`",
+        )
         .styles(styles)
+        .subcommand_negates_reqs(true)
+        .subcommand(with_pack_args(
+            Command::new("pack")
+                .about("Pack a PRX or a PBP, the default if no subcommand is given"),
+        ))
+        .subcommand(
+            Command::new("unpack")
+                .about("Decompress a packed PRX or PBP back to its ELF")
+                .arg(
+                    Arg::new("FILE")
+                        .help("The packed file")
+                        .long_help(
+                            "The packed file\n\nIf it is `-`, the file is read from the standard \
+                             input",
+                        )
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(output_arg().long_help(
+                    "Specify the output file\n\nIf it is `-`, the file is written to the standard \
+                     output. If this option is not specified, the unpacked file is written next \
+                     to <FILE> as `<stem>_unpacked.<ext>`, or `<STEM>_UNPACKED.<EXT>` for an \
+                     uppercase name, unless `--in-place` is set",
+                ))
+                .arg(
+                    Arg::new("in-place")
+                        .long("in-place")
+                        .short('i')
+                        .help("Overwrite <FILE> with the unpacked file")
+                        .conflicts_with("output")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(dry_run_arg()),
+        )
+        .subcommand(
+            Command::new("info")
+                .about("Print information about a file without packing it")
+                .long_about(
+                    "Print information about a file without packing it\n\nFor an already packed \
+                     file, the existing PSP header is printed instead",
+                )
+                .arg(file_arg())
+                .arg(
+                    Arg::new("color")
+                        .long("color")
+                        .help("When to color the output")
+                        .long_help(
+                            "When to color the output\n\nWith `auto`, the output is colored when \
+                             printed to a terminal and the `NO_COLOR` environment variable is not \
+                             set",
+                        )
+                        .value_name("WHEN")
+                        .value_parser(PossibleValuesParser::new(["auto", "always", "never"]))
+                        .default_value("auto"),
+                )
+                .arg(
+                    Arg::new("list-segments")
+                        .long("list-segments")
+                        .help("Also print the program headers of the ELF")
+                        .long_help(
+                            "Also print the program headers of the ELF\n\nThe ELF of a packed \
+                             file is decompressed first. The high bit of the physical address of \
+                             the module info segment marks a kernel module",
+                        )
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("list-sections")
+                        .long("list-sections")
+                        .help("Also print the section headers of the ELF")
+                        .long_help(
+                            "Also print the section headers of the ELF\n\nThe ELF of a packed \
+                             file is decompressed first. Names that can't be read from the \
+                             section name string table are printed as `?`",
+                        )
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("symbols")
+                        .long("symbols")
+                        .help("Also print the libraries the module exports and imports")
+                        .long_help(
+                            "Also print the libraries the module exports and imports\n\nThe \
+                             functions and variables of each library are listed by NID. The \
+                             unnamed library holding the module entry points is printed as \
+                             `(system)`",
+                        )
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Check that an already packed file is structurally sound")
+                .long_about(
+                    "Check that an already packed file is structurally sound\n\nEach check is \
+                     printed with PASS or FAIL, stopping on the first failure",
+                )
+                .arg(file_arg()),
+        )
+        .subcommand(
+            Command::new("compare")
+                .about("Print the PSP header fields that differ between two packed files")
+                .long_about(
+                    "Print the PSP header fields that differ between two packed files\n\nThe key \
+                     data is random and ignored unless `--include-keys` is passed. Exits with an \
+                     error if the headers differ",
+                )
+                .arg(file_arg())
+                .arg(
+                    Arg::new("OTHER")
+                        .help("The file to compare with")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("include-keys")
+                        .long("include-keys")
+                        .help("Also compare the key data")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print the completion script of a shell to stdout")
+                .arg(
+                    Arg::new("SHELL")
+                        .help("The shell to complete in")
+                        .required(true)
+                        .value_parser(value_parser!(Shell)),
+                ),
+        )
+        .subcommand(
+            Command::new("man")
+                .about("Print the man page in roff format to stdout")
+                .hide(true),
+        )
+        .subcommand(
+            Command::new("extract")
+                .about("Extract the sub-files of a PBP into a directory")
+                .arg(file_arg())
+                .arg(
+                    Arg::new("DIR")
+                        .help("The directory to extract to")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("make-pbp")
+                .about("Assemble a PBP from a PRX and loose sub-files without packing it")
+                .arg(
+                    Arg::new("FILE")
+                        .help("The PRX to put in the PBP")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .args(
+                    [
+                        ("sfo", "PARAM.SFO"),
+                        ("icon0", "ICON0.PNG"),
+                        ("icon1", "ICON1.PMF"),
+                        ("pic0", "PIC0.PNG"),
+                        ("pic1", "PIC1.PNG"),
+                        ("snd0", "SND0.AT3"),
+                        ("psar", "DATA.PSAR"),
+                    ]
+                    .map(|(name, file)| {
+                        Arg::new(name)
+                            .long(name)
+                            .help(format!("The {file} to put in the PBP"))
+                            .value_name(file)
+                            .required(name == "sfo")
+                            .value_parser(value_parser!(PathBuf))
+                    }),
+                )
+                .arg(output_arg().long_help(
+                    "Specify the output file\n\nIf it is `-`, the file is written to the standard \
+                     output. If this option is not specified, the PBP is written to `EBOOT.PBP`",
+                ))
+                .arg(dry_run_arg()),
+        )
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .help("Verbose output to stderr")
+                .long_help(
+                    "Verbose output to stderr\n\nCurrently, it mostly output warning messages and \
+                     some compression information",
+                )
+                .global(true)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .short('q')
+                .help("Only output errors to stderr")
+                .long_help(
+                    "Only output errors to stderr\n\nWarnings, like unknown tags, are not printed",
+                )
+                .conflicts_with("verbose")
+                .global(true)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("list-tags")
+                .long("list-tags")
+                .help("List the known tags and exit")
+                .long_help(
+                    "List the known tags and exit\n\nEach line has the tag, the OE tag, the \
+                     executable kind they are the default for and a description, separated by tabs",
+                )
+                .exclusive(true)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("list-error-codes")
+                .long("list-error-codes")
+                .help("List the exit codes used on errors and exit")
+                .long_help(
+                    "List the exit codes used on errors and exit\n\nEach line has the exit code, \
+                     the error name and its message, separated by tabs",
+                )
+                .exclusive(true)
+                .action(ArgAction::SetTrue),
+        );
+
+    #[cfg(feature = "logging")]
+    let app = app.arg(
+        Arg::new("log-level")
+            .long("log-level")
+            .help("The maximum level of the messages printed to stderr")
+            .long_help(
+                "The maximum level of the messages printed to stderr\n\nDefaults to the \
+                 `RUST_LOG` environment variable if it is a level, `info` with `--verbose`, \
+                 `error` with `--quiet` and `warn` otherwise. The `debug` and `trace` levels also \
+                 show the packing steps",
+            )
+            .value_name("LEVEL")
+            .value_parser(PossibleValuesParser::new([
+                "off", "error", "warn", "info", "debug", "trace",
+            ]))
+            .global(true),
+    );
+
+    // Without a subcommand, the arguments are the ones of `pack`
+    with_pack_args(app).mut_arg("FILE", |arg| {
+        arg.required(false)
+            .required_unless_present_any(["list-error-codes", "list-tags"])
+    })
+}
+
+/// Add the arguments of the `pack` subcommand to `cmd`.
+fn with_pack_args(cmd: Command) -> Command {
+    let cmd = cmd
         .arg(
             Arg::new("FILE")
                 .help("The file to be packed.")
+                .long_help(
+                    "The file to be packed.\n\nIf it is `-`, the file is read from the standard \
+                     input",
+                )
                 .required(true)
                 .value_parser(value_parser!(PathBuf)),
         )
@@ -30,38 +305,451 @@ pub(crate) fn create_app() -> Command {
                 .long("tags")
                 .short('s')
                 .help("The tags to use")
+                .long_help(
+                    "The tags to use\n\nEach tag is decimal, or hexadecimal with the `0x` prefix \
+                     (e.g. `--tags 0x457B06F0 0x8555ABF2`)",
+                )
                 .num_args(2)
                 .value_names(["TAG", "OE_TAG"])
-                .value_parser(value_parser!(u32)),
+                .value_parser(parse_u32),
+        )
+        .arg(
+            Arg::new("preset")
+                .long("preset")
+                .short('p')
+                .help("Use the default tags of an executable kind")
+                .long_help(
+                    "Use the default tags of an executable kind\n\n`user-5xx` is the same as \
+                     `user`, named after the firmware its tags target. These are the only tags \
+                     the custom firmware loaders accept, so there are no other presets. The \
+                     `tags` option takes precedence over this option",
+                )
+                .value_name("PRESET")
+                .value_parser(PossibleValuesParser::new(config::PRESETS)),
+        )
+        .arg(
+            Arg::new("kind")
+                .long("kind")
+                .help("Pack as this executable kind instead of the detected one")
+                .long_help(
+                    "Pack as this executable kind instead of the detected one\n\nForcing `user` \
+                     or `kernel` also clears or sets the kernel mode attribute of the module. A \
+                     PRX cannot be packed as `pbp`, nor a PBP as `user` or `kernel`",
+                )
+                .value_name("KIND")
+                .value_parser(PossibleValuesParser::new(["user", "kernel", "pbp"])),
+        )
+        .arg(
+            Arg::new("level")
+                .long("level")
+                .short('l')
+                .help("The gzip compression level")
+                .long_help(
+                    "The gzip compression level\n\nGoes from 0 (no compression) to 9 (best \
+                     compression). Lower levels are faster, useful for iterative development \
+                     builds",
+                )
+                .value_name("LEVEL")
+                .default_value("9")
+                .value_parser(value_parser!(u32).range(0..=9)),
+        )
+        .arg(
+            Arg::new("devkit-version")
+                .long("devkit-version")
+                .help("The devkit version to write in the PSP header, in hexadecimal")
+                .long_help(
+                    "The devkit version to write in the PSP header, in hexadecimal\n\nOverrides \
+                     the version implied by the executable kind, e.g. `0x05070210`",
+                )
+                .value_name("VERSION")
+                .value_parser(parse_hex_u32),
+        )
+        .arg(
+            Arg::new("comp-attribute")
+                .long("comp-attribute")
+                .help("The compression attribute to write in the PSP header, in hexadecimal")
+                .long_help(
+                    "The compression attribute to write in the PSP header, in hexadecimal\n\nFor \
+                     research on the loader: overrides the attribute of the compression method \
+                     (`0x1` for gzip, `0x0` when stored) without changing how the payload is \
+                     compressed. Unknown values are an error with `--strict`",
+                )
+                .value_name("ATTRIBUTE")
+                .value_parser(parse_hex_u16),
         )
         .arg(
-            Arg::new("dry-run")
-                .long("dry-run")
-                .short('n')
-                .help("Don't actually write the compressed file")
+            Arg::new("decrypt-mode")
+                .long("decrypt-mode")
+                .help("The decrypt mode to write in the PSP header")
+                .long_help(
+                    "The decrypt mode to write in the PSP header\n\nOverrides the mode implied by \
+                     the module attributes",
+                )
+                .value_name("MODE")
+                .value_parser(PossibleValuesParser::new(DecryptMode::ALL.map(DecryptMode::name))),
+        )
+        .arg(
+            Arg::new("module-version")
+                .long("module-version")
+                .help("The module version to write in the PSP header and module info")
+                .value_name("MAJOR.MINOR")
+                .value_parser(parse_module_version),
+        )
+        .arg(
+            Arg::new("name")
+                .long("name")
+                .help("The module name to write in the PSP header and module info")
+                .long_help(
+                    "The module name to write in the PSP header and module info\n\nLonger names \
+                     are truncated to 26 bytes",
+                )
+                .value_name("NAME"),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .short('f')
+                .help("Unpack and repack files that are already packed")
+                .long_help(
+                    "Unpack and repack files that are already packed\n\nUseful to change the tags \
+                     or other options of a packed file without its original ELF",
+                )
                 .action(ArgAction::SetTrue),
         )
         .arg(
-            Arg::new("verbose")
-                .long("verbose")
-                .short('v')
-                .help("Verbose output to stderr")
+            Arg::new("preserve-reserved")
+                .long("preserve-reserved")
+                .help("Keep the reserved fields of the original header when repacking")
                 .long_help(
-                    "Verbose output to stderr\n\nCurrently, it mostly output warning messages and \
-                     some compression information",
+                    "Keep the reserved fields of the original header when repacking\n\nCopies the \
+                     `reserved`, `reserved2` and `scheck` fields, where some encrypters stash \
+                     data, instead of zeroing them",
                 )
+                .requires("force")
                 .action(ArgAction::SetTrue),
         )
         .arg(
-            Arg::new("output")
-                .long("output")
-                .short('o')
-                .help("Specify the output file")
+            Arg::new("allow-no-bss")
+                .long("allow-no-bss")
+                .help("Pack ELFs without a `.bss` section with a BSS size of 0")
                 .long_help(
-                    "Specify the output file\n\nIf this option is not specified, the program will \
-                     overwrite the passed <FILE>",
+                    "Pack ELFs without a `.bss` section with a BSS size of 0\n\nOnly relevant \
+                     with `--bss-from section`. On hardware, the module then has no \
+                     zero-initialized region. Without this flag, a missing `.bss` section is an \
+                     error",
                 )
-                .value_name("OUT_FILE")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("bss-from")
+                .long("bss-from")
+                .help("Where to read the BSS size from [default: segments]")
+                .long_help(
+                    "Where to read the BSS size from [default: segments]\n\n`segments` sums the \
+                     memory size past the file size of the loadable segments, which survives \
+                     stripping, and falls back to the `.bss` section if that is 0. `section` uses \
+                     the size of the `.bss` section",
+                )
+                .value_name("SOURCE")
+                .value_parser(PossibleValuesParser::new(BssSource::ALL.map(BssSource::name))),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .help("Seed the random key data for reproducible output")
+                .long_help(
+                    "Seed the random key data for reproducible output\n\nPacking the same file \
+                     with the same options and seed produces byte-identical output",
+                )
+                .value_name("SEED")
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("header")
+                .long("header")
+                .help("Reuse the key data of a PSP header written by `--dump-header`")
+                .long_help(
+                    "Reuse the key data of a PSP header written by `--dump-header`\n\nThe sizes \
+                     and segments are still computed for <FILE>. The signature check block \
+                     (`scheck`), zeroed otherwise, is also reused. Without `--tags` or \
+                     `--preset`, the tags of the header are also reused. With the same options, \
+                     this reproduces the packed file the header comes from",
+                )
+                .value_name("HEADER")
+                .conflicts_with_all(["seed", "no-random"])
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("self-check")
+                .long("self-check")
+                .help("Check that the packed file decompresses to the input ELF before writing it")
+                .long_help(
+                    "Check that the packed file decompresses to the input ELF before writing \
+                     it\n\nOn mismatch, nothing is written and the program fails",
+                )
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("overlap")
+                .long("overlap")
+                .help("Let the loader decompress the module in place (experimental)")
+                .long_help(
+                    "Let the loader decompress the module in place (experimental)\n\nSets the \
+                     overlap size of the header: how many bytes of the compressed payload, at the \
+                     end of the load buffer, can be overwritten by the decompressed ELF. The \
+                     loader then needs less memory on top of the ELF size. Without this flag, the \
+                     overlap size is 0 and the payload never overlaps the ELF",
+                )
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("pad-to")
+                .long("pad-to")
+                .help("Zero-pad the packed PRX to a multiple of N bytes")
+                .long_help(
+                    "Zero-pad the packed PRX to a multiple of N bytes\n\nN must be a power of \
+                     two, e.g. 16 or 2048 for a sector. The PSP size of the header includes the \
+                     padding. In a PBP, DATA.PSP is padded and DATA.PSAR moved after it. Without \
+                     this option, the output is not padded",
+                )
+                .value_name("N")
+                .value_parser(parse_pad_to),
+        )
+        .arg(
+            Arg::new("strip")
+                .long("strip")
+                .help("Strip the sections the loader doesn't need before packing")
+                .long_help(
+                    "Strip the sections the loader doesn't need before packing\n\nDrops the \
+                     section header table and everything after the last segment, and zeroes the \
+                     `.comment` and `.debug*` sections. The program headers, segments and module \
+                     info are kept. The bytes saved are reported with `--verbose`. Fails if a \
+                     PRX_RELOC section is outside the relocation segments, as it would be lost",
+                )
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-random")
+                .long("no-random")
+                .help("Leave the header key data zeroed")
+                .long_help(
+                    "Leave the header key data zeroed\n\nThe output is deterministic, like with \
+                     `--seed`, but without filling the key data at all",
+                )
+                .conflicts_with("seed")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Treat warnings about the input as errors")
+                .long_help(
+                    "Treat warnings about the input as errors\n\nFor example, tags that are not \
+                     known to be valid, or unknown bits in the module info attribute",
+                )
+                .action(ArgAction::SetTrue),
+        )
+        .arg(dry_run_arg())
+        .arg(
+            Arg::new("stats-only")
+                .long("stats-only")
+                .help("Only print the sizes and ratio the packed file would have")
+                .long_help(
+                    "Only print the sizes and ratio the packed file would have\n\nThe file is \
+                     fully compressed, but nothing is written, even with `--output`. One line per \
+                     file is printed to stdout, or the `--json` object",
+                )
+                .conflicts_with_all(["dry-run", "verbose", "backup", "dump-header"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Print the pack statistics as JSON to stdout")
+                .long_help(
+                    "Print the pack statistics as JSON to stdout\n\nOne object per packed file, \
+                     on its own line, with the original and compressed sizes, the ratio, the \
+                     kind, the decrypt mode, the devkit version and the tags. Warnings still go \
+                     to stderr",
+                )
+                .conflicts_with("verbose")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("progress")
+                .long("progress")
+                .help("Show the compression progress on stderr")
+                .long_help(
+                    "Show the compression progress on stderr\n\nNothing is shown if stderr is not \
+                     a terminal or with `--quiet`",
+                )
+                .action(ArgAction::SetTrue),
+        )
+        .arg(output_arg().long_help(
+            "Specify the output file\n\nIf it is `-`, the file is written to the standard output. \
+             If this option is not specified, the packed file is written next to <FILE> as \
+             `<stem>_packed.<ext>`, or `<STEM>_PACKED.<EXT>` for an uppercase name (e.g. \
+             `EBOOT_PACKED.PBP`), unless `--in-place` is set. Without an extension, `.prx` or \
+             `.PBP` is used depending on the executable kind",
+        ))
+        .arg(
+            Arg::new("in-place")
+                .long("in-place")
+                .short('i')
+                .help("Overwrite <FILE> with the packed file")
+                .long_help(
+                    "Overwrite <FILE> with the packed file\n\nWith `--recursive`, every packed \
+                     file overwrites its input. Without this flag, the input is never overwritten",
+                )
+                .conflicts_with_all(["output", "output-dir"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dump-header")
+                .long("dump-header")
+                .help("Also write the raw PSP header of the packed file to PATH")
+                .long_help(
+                    "Also write the raw PSP header of the packed file to PATH\n\nThe header is \
+                     written even with `--dry-run`, to inspect or patch it without the packed file",
+                )
+                .value_name("PATH")
+                .conflicts_with("recursive")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("backup")
+                .long("backup")
+                .help("Copy the input file to `<FILE>.bak` before overwriting it")
+                .long_help(
+                    "Copy the input file to `<FILE>.bak` before overwriting it\n\nIf that file \
+                     already exists, a `.N` suffix is added to the backup name",
+                )
+                .requires("in-place")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("recursive")
+                .long("recursive")
+                .short('r')
+                .help("Pack every PRX and PBP under <FILE> if it is a directory")
+                .long_help(
+                    "Pack every PRX and PBP under <FILE> if it is a directory\n\nFiles that are \
+                     already packed are skipped",
+                )
+                .conflicts_with("output")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("output-dir")
+                .long("output-dir")
+                .help("Write the packed files to DIR instead of next to the input files")
+                .long_help(
+                    "Write the packed files to DIR instead of next to the input files\n\nWith \
+                     `--recursive`, the paths relative to <FILE> are kept under DIR. Missing \
+                     directories are created",
+                )
+                .value_name("DIR")
+                .conflicts_with("output")
                 .value_parser(value_parser!(PathBuf)),
+        );
+
+    #[cfg(feature = "rayon")]
+    let cmd = cmd.arg(
+        Arg::new("jobs")
+            .long("jobs")
+            .short('j')
+            .help("The number of files to pack in parallel with `--recursive`")
+            .long_help(
+                "The number of files to pack in parallel with `--recursive`\n\nDefaults to the \
+                 number of CPU cores. With 1, the files are packed one after the other. Memory \
+                 use grows with the number of jobs, as each one holds a file and its packed output",
+            )
+            .value_name("N")
+            .requires("recursive")
+            .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+    );
+
+    cmd
+}
+
+/// The `<FILE>` argument of the subcommands that only read it.
+fn file_arg() -> Arg {
+    Arg::new("FILE")
+        .help("The file to read")
+        .long_help("The file to read\n\nIf it is `-`, the file is read from the standard input")
+        .required(true)
+        .value_parser(value_parser!(PathBuf))
+}
+
+fn output_arg() -> Arg {
+    Arg::new("output")
+        .long("output")
+        .short('o')
+        .help("Specify the output file")
+        .long_help(
+            "Specify the output file\n\nIf it is `-`, the file is written to the standard output",
         )
+        .value_name("OUT_FILE")
+        .value_parser(value_parser!(PathBuf))
+}
+
+fn dry_run_arg() -> Arg {
+    Arg::new("dry-run")
+        .long("dry-run")
+        .short('n')
+        .help("Don't actually write the output file")
+        .action(ArgAction::SetTrue)
+}
+
+/// Parse a hexadecimal `u32`, with or without the `0x` prefix.
+fn parse_hex_u32(s: &str) -> Result<u32, String> {
+    let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+    u32::from_str_radix(digits, 16).map_err(|e| format!("`{s}` is not a hexadecimal u32: {e}"))
+}
+
+/// Parse a hexadecimal `u16`, with or without the `0x` prefix.
+fn parse_hex_u16(s: &str) -> Result<u16, String> {
+    let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+    u16::from_str_radix(digits, 16).map_err(|e| format!("`{s}` is not a hexadecimal u16: {e}"))
+}
+
+/// Parse a `--pad-to` alignment, a power of two.
+fn parse_pad_to(s: &str) -> Result<u32, String> {
+    let align = s.parse::<u32>().map_err(|e| format!("`{s}` is not a u32: {e}"))?;
+    if !align.is_power_of_two() {
+        return Err(format!("`{s}` is not a power of two"));
+    }
+    Ok(align)
+}
+
+/// Parse a decimal or `0x` prefixed hexadecimal `u32`, like the config file.
+fn parse_u32(s: &str) -> Result<u32, String> {
+    config::parse_integer(s)
+        .ok_or_else(|| format!("`{s}` is not a decimal or `0x` prefixed hexadecimal u32"))
+}
+
+/// Parse a `(psp_tag, oe_tag)` pair of values separated by whitespace or a comma, see
+/// [`parse_u32`].
+pub(crate) fn parse_tags(s: &str) -> Result<(u32, u32), String> {
+    let tags = s
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|tag| !tag.is_empty())
+        .map(parse_u32)
+        .collect::<Result<Vec<_>, _>>()?;
+    match tags[..] {
+        [psp_tag, oe_tag] => Ok((psp_tag, oe_tag)),
+        _ => Err(format!("`{s}` is not two tags")),
+    }
+}
+
+/// Parse a `MAJOR.MINOR` module version, where both parts fit in a `u8`.
+fn parse_module_version(s: &str) -> Result<(u8, u8), String> {
+    let (major, minor) = s
+        .split_once('.')
+        .ok_or_else(|| format!("`{s}` is not in the MAJOR.MINOR format"))?;
+    let parse = |part: &str| {
+        part.parse::<u8>()
+            .map_err(|_| format!("`{part}` is not a version number from 0 to 255"))
+    };
+    Ok((parse(major)?, parse(minor)?))
 }
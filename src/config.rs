@@ -0,0 +1,150 @@
+//! Packing defaults read from a `psp-packer.toml` file.
+//!
+//! Only the subset of TOML needed by the supported keys is understood:
+//!
+//! ```toml
+//! # Comments and blank lines are ignored
+//! tags = [0x457B06F0, 0x8555ABF2]
+//! level = 9
+//! preset = "user"
+//! ```
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use psp_packer::ExecutableKind;
+
+/// Name of the configuration file.
+const CONFIG_FILE: &str = "psp-packer.toml";
+
+/// Defaults for the options of the same name, the command line options take precedence.
+#[derive(Default)]
+pub(crate) struct Config {
+    pub(crate) tags: Option<(u32, u32)>,
+    pub(crate) level: Option<u32>,
+    pub(crate) preset: Option<ExecutableKind>,
+}
+
+impl Config {
+    /// Load the first configuration file found, in the current directory then in
+    /// `$XDG_CONFIG_HOME` (`~/.config` if not set).
+    ///
+    /// Returns the path of the loaded file, or `None` with the default configuration if there is
+    /// none.
+    pub(crate) fn load() -> Result<(Option<PathBuf>, Self), String> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+
+        let candidates =
+            [Some(PathBuf::from(CONFIG_FILE)), config_home.map(|dir| dir.join(CONFIG_FILE))];
+        for path in candidates.into_iter().flatten() {
+            let src = match fs::read_to_string(&path) {
+                Ok(src) => src,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(format!("failed to read `{}`: {e}", path.display())),
+            };
+            let config = Self::parse(&src).map_err(|e| format!("`{}`: {e}", path.display()))?;
+            return Ok((Some(path), config));
+        }
+
+        Ok((None, Self::default()))
+    }
+
+    fn parse(src: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+
+        for (i, line) in src.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = |msg: &str| format!("line {}: {msg}", i + 1);
+            let (key, value) =
+                line.split_once('=').ok_or_else(|| error("expected `key = value`"))?;
+            let value = value.trim();
+
+            match key.trim() {
+                "tags" => {
+                    let tags = value
+                        .strip_prefix('[')
+                        .and_then(|tags| tags.strip_suffix(']'))
+                        .ok_or_else(|| error("`tags` must be an array of two integers"))?
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|tag| !tag.is_empty())
+                        .map(parse_integer)
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| error("`tags` must be an array of two integers"))?;
+                    let [psp_tag, oe_tag] = tags[..] else {
+                        return Err(error("`tags` must be an array of two integers"));
+                    };
+                    config.tags = Some((psp_tag, oe_tag));
+                },
+                "level" => {
+                    let level = parse_integer(value)
+                        .filter(|level| *level <= 9)
+                        .ok_or_else(|| error("`level` must be an integer from 0 to 9"))?;
+                    config.level = Some(level);
+                },
+                "preset" => {
+                    let preset = value
+                        .strip_prefix('"')
+                        .and_then(|preset| preset.strip_suffix('"'))
+                        .and_then(preset_kind)
+                        .ok_or_else(|| error("`preset` must be \"user\", \"kernel\" or \"pbp\""))?;
+                    config.preset = Some(preset);
+                },
+                key => return Err(error(&format!("unknown key `{key}`"))),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Names of the `--preset` values.
+///
+/// `user-5xx` names the user tags after the firmware they target. There are no other firmware
+/// specific presets, as the tags of [`KNOWN_TAGS`](psp_packer::KNOWN_TAGS) are the only ones the
+/// custom firmware loaders accept for `~PSP` modules.
+pub(crate) const PRESETS: [&str; 4] = ["user", "user-5xx", "kernel", "pbp"];
+
+/// Returns the executable kind of a `--preset` value.
+pub(crate) fn preset_kind(preset: &str) -> Option<ExecutableKind> {
+    match preset {
+        "user" | "user-5xx" => Some(ExecutableKind::UserPrx),
+        "kernel" => Some(ExecutableKind::KernelPrx),
+        "pbp" => Some(ExecutableKind::Pbp),
+        _ => None,
+    }
+}
+
+/// Remove a `#` comment, unless it is in a string.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {},
+        }
+    }
+    line
+}
+
+/// Parse a decimal or `0x` prefixed hexadecimal `u32`, with optional `_` separators.
+///
+/// This is how every tag is parsed, in the config file, on the command line and in
+/// `PSP_PACKER_TAGS`.
+pub(crate) fn parse_integer(s: &str) -> Option<u32> {
+    let s = s.replace('_', "");
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(digits) => u32::from_str_radix(digits, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
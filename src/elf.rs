@@ -4,12 +4,44 @@ use crate::{
 };
 
 const ELF_MAGIC: u32 = 0x464C457F;
+const ET_REL: u16 = 1;
+const ET_EXEC: u16 = 2;
 const ELF_TYPE_PRX: u16 = 0xFFA0;
+const EM_MIPS: u16 = 8;
 
+const ELF_CLASS_32: u8 = 1;
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_LSB: u8 = 1;
+
+const PT_NULL: u32 = 0;
+/// Loadable segment, described in the PSP header.
+const PT_LOAD: u32 = 1;
+const PT_MIPS_REGINFO: u32 = 0x70000000;
+/// PRX relocations of the old format, applied by the loader from the ELF itself.
+const PT_PRX_RELOC: u32 = 0x700000A0;
+/// PRX relocations of the new (compressed) format.
+const PT_PRX_RELOC2: u32 = 0x700000A1;
+
+const SHT_NULL: u32 = 0;
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_NOTE: u32 = 7;
+/// Section occupying no space in the file, e.g. `.bss`.
+const SHT_NOBITS: u32 = 8;
+const SHT_REL: u32 = 9;
+const SHT_MIPS_REGINFO: u32 = 0x70000006;
+/// PRX relocations, the section counterpart of [`PT_PRX_RELOC`].
+const SHT_PRX_RELOC: u32 = 0x700000A0;
+
+/// An ELF header.
 #[repr(C)]
 #[derive(Clone)]
 #[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Elf32Ehdr {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_u32"))]
     pub e_magic: u32,
     pub e_class: u8,
     pub e_data: u8,
@@ -35,6 +67,14 @@ impl TryFromBytes for Elf32Ehdr {
         if src.e_magic != ELF_MAGIC {
             return Err(Error::NotElf);
         }
+        match src.e_class {
+            ELF_CLASS_32 => {},
+            ELF_CLASS_64 => return Err(Error::Not32Bit),
+            _ => return Err(Error::NotElf),
+        }
+        if src.e_data != ELF_DATA_LSB {
+            return Err(Error::NotLittleEndian(src.e_data));
+        }
         Ok(src)
     }
 }
@@ -46,8 +86,28 @@ impl Elf32Ehdr {
     pub fn is_prx(&self) -> bool {
         self.e_type == ELF_TYPE_PRX
     }
+
+    /// Check that the ELF is a PRX, failing with [`Error::WrongElfType`] for the object files
+    /// and static executables commonly passed by mistake, and [`Error::NotPrx`] otherwise.
+    pub fn validate_prx(&self) -> Result<(), Error> {
+        match self.e_type {
+            _ if self.is_prx() => Ok(()),
+            ET_REL | ET_EXEC => Err(Error::WrongElfType(self.e_type)),
+            _ => Err(Error::NotPrx),
+        }
+    }
+
+    /// Check that the ELF is for the MIPS CPU of the PSP.
+    pub fn validate_machine(&self) -> Result<(), Error> {
+        if self.e_machine == EM_MIPS {
+            Ok(())
+        } else {
+            Err(Error::NotMips(self.e_machine))
+        }
+    }
 }
 
+/// An ELF program header, describing a segment.
 #[repr(C)]
 #[derive(Clone)]
 #[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
@@ -70,6 +130,30 @@ impl TryFromBytes for Elf32Phdr {
 
 impl AsBytes for Elf32Phdr {}
 
+impl Elf32Phdr {
+    #[inline]
+    pub fn is_load(&self) -> bool {
+        self.p_type == PT_LOAD
+    }
+
+    /// Whether this is a PRX relocation segment, of either format.
+    #[inline]
+    pub fn is_reloc(&self) -> bool {
+        matches!(self.p_type, PT_PRX_RELOC | PT_PRX_RELOC2)
+    }
+
+    /// Returns the name of the segment type, `None` if it is not one found in PRXs.
+    pub fn type_name(&self) -> Option<&'static str> {
+        match self.p_type {
+            PT_NULL => Some("NULL"),
+            PT_LOAD => Some("LOAD"),
+            PT_MIPS_REGINFO => Some("MIPS_REGINFO"),
+            PT_PRX_RELOC => Some("PRX_RELOC"),
+            PT_PRX_RELOC2 => Some("PRX_RELOC2"),
+            _ => None,
+        }
+    }
+}
 
 #[repr(C)]
 #[derive(Clone)]
@@ -94,3 +178,34 @@ impl TryFromBytes for Elf32Shdr {
 }
 
 impl AsBytes for Elf32Shdr {}
+
+impl Elf32Shdr {
+    /// Whether the section occupies no space in the file, like `.bss`.
+    #[inline]
+    pub fn is_nobits(&self) -> bool {
+        self.sh_type == SHT_NOBITS
+    }
+
+    /// Whether this is a PRX relocation section, only kept by the loader if a segment covers it.
+    #[inline]
+    pub fn is_reloc(&self) -> bool {
+        self.sh_type == SHT_PRX_RELOC
+    }
+
+    /// Returns the name of the section type, `None` if it is not one found in PRXs.
+    pub fn type_name(&self) -> Option<&'static str> {
+        match self.sh_type {
+            SHT_NULL => Some("NULL"),
+            SHT_PROGBITS => Some("PROGBITS"),
+            SHT_SYMTAB => Some("SYMTAB"),
+            SHT_STRTAB => Some("STRTAB"),
+            SHT_RELA => Some("RELA"),
+            SHT_NOTE => Some("NOTE"),
+            SHT_NOBITS => Some("NOBITS"),
+            SHT_REL => Some("REL"),
+            SHT_MIPS_REGINFO => Some("MIPS_REGINFO"),
+            SHT_PRX_RELOC => Some("PRX_RELOC"),
+            _ => None,
+        }
+    }
+}
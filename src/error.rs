@@ -1,10 +1,14 @@
-use std::{ffi::FromBytesUntilNulError, fmt, io};
+use std::{
+    ffi::{CStr, FromBytesUntilNulError},
+    fmt, io,
+};
 
 pub enum Error {
     AlreadyPacked,
     FromBytes {
         input_len: usize,
         expected_len: Option<usize>,
+        context: Option<&'static str>,
     },
     FileTooSmall,
     NotElf,
@@ -19,30 +23,144 @@ pub enum Error {
     Alignment {
         align: usize,
         addr: usize,
+        context: Option<&'static str>,
     },
     FileTooBig,
-    CStr(FromBytesUntilNulError),
+    CStr {
+        error: FromBytesUntilNulError,
+        context: Option<&'static str>,
+    },
+    NotSfo,
+    SfoTruncated(&'static str),
+    NotPacked,
+    Corrupted(&'static str),
+    UnknownTags {
+        tag: u32,
+        oe_tag: u32,
+    },
+    Not32Bit,
+    NotLittleEndian(u8),
+    Encrypted,
+    NotMips(u16),
+    SegmentAlign(u32),
+    SelfCheck,
+    MalformedPbp {
+        section: &'static str,
+        offset: u32,
+    },
+    HeadersDiffer,
+    PadAlign(u32),
+    SizeOverflow {
+        field: &'static str,
+        size: u64,
+    },
+    TooManySegments(usize),
+    UnknownAttribute(u16),
+    UnknownCompAttribute(u16),
+    BadLibraryTable(u32),
+    WrongElfType(u16),
+    EmptyFile,
+    UncoveredRelocs(u32),
+}
+
+/// Generate [`Error::error_code`], [`Error::name`] and [`Error::variants`] from one table of
+/// `code Variant => sample value`, so that a variant can't be missing from any of them: the
+/// matches fail to compile without it.
+macro_rules! error_table {
+    ($($code:literal $name:ident => $sample:expr,)+) => {
+        impl Error {
+            pub fn error_code(&self) -> i32 {
+                match self {
+                    $(Error::$name { .. } => $code,)+
+                }
+            }
+
+            /// The variant name of the error.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Error::$name { .. } => stringify!($name),)+
+                }
+            }
+
+            /// One value of every error variant, sorted by [`error_code`](Self::error_code).
+            ///
+            /// Variants that carry data are filled with placeholder values.
+            pub fn variants() -> Vec<Error> {
+                vec![$($sample),+]
+            }
+        }
+    };
+}
+
+error_table! {
+    101 Io => Error::Io(io::Error::other("I/O error")),
+    102 AlreadyPacked => Error::AlreadyPacked,
+    103 NotPrx => Error::NotPrx,
+    104 NotPbp => Error::NotPbp,
+    105 NotElf => Error::NotElf,
+    106 NoModuleInfo => Error::NoModuleInfo,
+    107 FileTooBig => Error::FileTooBig,
+    108 FileTooSmall => Error::FileTooSmall,
+    109 KernelPbp => Error::KernelPbp,
+    110 MixedPrivileges => Error::MixedPrivileges,
+    111 NoSegments => Error::NoSegments,
+    112 BssNotFound => Error::BssNotFound,
+    113 FromBytes => Error::FromBytes {
+        input_len: 0,
+        expected_len: None,
+        context: None,
+    },
+    114 Alignment => Error::Alignment {
+        align: 0,
+        addr: 0,
+        context: None,
+    },
+    115 CStr => Error::CStr {
+        error: CStr::from_bytes_until_nul(b"").unwrap_err(),
+        context: None,
+    },
+    116 NotSfo => Error::NotSfo,
+    117 SfoTruncated => Error::SfoTruncated("table"),
+    118 NotPacked => Error::NotPacked,
+    119 Corrupted => Error::Corrupted("check"),
+    120 UnknownTags => Error::UnknownTags { tag: 0, oe_tag: 0 },
+    121 Not32Bit => Error::Not32Bit,
+    122 NotLittleEndian => Error::NotLittleEndian(2),
+    123 Encrypted => Error::Encrypted,
+    124 NotMips => Error::NotMips(0),
+    125 SegmentAlign => Error::SegmentAlign(0x10000),
+    126 SelfCheck => Error::SelfCheck,
+    127 MalformedPbp => Error::MalformedPbp {
+        section: "DATA.PSAR",
+        offset: 0,
+    },
+    128 HeadersDiffer => Error::HeadersDiffer,
+    129 PadAlign => Error::PadAlign(3),
+    130 SizeOverflow => Error::SizeOverflow {
+        field: "elf_size",
+        size: 1 << 32,
+    },
+    131 TooManySegments => Error::TooManySegments(5),
+    132 UnknownAttribute => Error::UnknownAttribute(0x0005),
+    133 UnknownCompAttribute => Error::UnknownCompAttribute(0x2),
+    134 BadLibraryTable => Error::BadLibraryTable(0x1000),
+    135 WrongElfType => Error::WrongElfType(2),
+    136 EmptyFile => Error::EmptyFile,
+    137 UncoveredRelocs => Error::UncoveredRelocs(0x1000),
 }
 
 impl Error {
-    pub fn error_code(&self) -> i32 {
-        match self {
-            Error::Io(_) => 101,
-            Error::AlreadyPacked => 102,
-            Error::NotPrx => 103,
-            Error::NotPbp => 104,
-            Error::NotElf => 105,
-            Error::NoModuleInfo => 106,
-            Error::FileTooBig => 107,
-            Error::FileTooSmall => 108,
-            Error::KernelPbp => 109,
-            Error::MixedPrivileges => 110,
-            Error::NoSegments => 111,
-            Error::BssNotFound => 112,
-            Error::FromBytes { .. } => 113,
-            Error::Alignment { .. } => 114,
-            Error::CStr(_) => 115,
+    /// Name the structure that failed to be read, for the errors that can carry it.
+    ///
+    /// Errors that already have a context, or that can't carry one, are returned unchanged.
+    pub(crate) fn context(mut self, structure: &'static str) -> Self {
+        if let Error::FromBytes { context, .. }
+        | Error::Alignment { context, .. }
+        | Error::CStr { context, .. } = &mut self
+        {
+            context.get_or_insert(structure);
         }
+        self
     }
 }
 
@@ -54,7 +172,10 @@ impl From<io::Error> for Error {
 
 impl From<FromBytesUntilNulError> for Error {
     fn from(value: FromBytesUntilNulError) -> Self {
-        Self::CStr(value)
+        Self::CStr {
+            error: value,
+            context: None,
+        }
     }
 }
 
@@ -62,8 +183,18 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Error::AlreadyPacked => f.pad("file already packed"),
-            Error::FromBytes { .. } => {
-                write!(f, "the program had a internal type conversion error: {self:?}")
+            Error::FromBytes {
+                input_len,
+                expected_len,
+                context,
+            } => {
+                write!(f, "failed to read {}", context.unwrap_or("a structure"))?;
+                match expected_len {
+                    Some(expected_len) => {
+                        write!(f, ": needed {expected_len} bytes, got {input_len}")
+                    },
+                    None => write!(f, ": got {input_len} bytes"),
+                }
             },
             Error::FileTooSmall => f.pad("the file is smaller than expected"),
             Error::NotElf => f.pad("no elf found"),
@@ -76,14 +207,107 @@ impl fmt::Display for Error {
             Error::MixedPrivileges => {
                 f.pad("the file has mixed privileges between the elf and module info data")
             },
-            Error::NoSegments => f.pad("the elf part of the file has no segments"),
+            Error::NoSegments => f.pad(
+                "the elf part of the file has no loadable segments, it may be an object file \
+                 (`.o`) that is not linked",
+            ),
             Error::BssNotFound => f.pad("the elf part of the file do not have a `.bss` section"),
             Error::Io(error) => write!(f, "{error}"),
-            Error::Alignment { .. } => {
-                write!(f, "the program had a internal type conversion error: {self:?}")
-            },
+            Error::Alignment {
+                align,
+                addr,
+                context,
+            } => write!(
+                f,
+                "failed to read {}: address 0x{addr:08X} is not aligned to {align} bytes",
+                context.unwrap_or("a structure")
+            ),
             Error::FileTooBig => f.pad("the file is bigger than expected for a PSP file"),
-            Error::CStr(e) => write!(f, "the program had a internal type conversion error: {e}"),
+            Error::CStr { error, context } => {
+                write!(f, "failed to read {}: {error}", context.unwrap_or("a string"))
+            },
+            Error::NotSfo => f.pad("the PBP does not have a valid PARAM.SFO"),
+            Error::SfoTruncated(table) => write!(f, "the PARAM.SFO {table} is truncated"),
+            Error::NotPacked => f.pad("the file is not packed"),
+            Error::Corrupted(check) => write!(f, "the packed file is corrupted: {check}"),
+            Error::UnknownTags { tag, oe_tag } => write!(
+                f,
+                "unknown tags 0x{tag:08X} 0x{oe_tag:08X}, the module will not decrypt on hardware"
+            ),
+            Error::Not32Bit => f.pad("the elf is 64 bits, the PSP only runs 32 bits executables"),
+            Error::NotLittleEndian(e_data) => write!(
+                f,
+                "the elf is not little-endian (e_data is {e_data}), as expected for the PSP"
+            ),
+            Error::Encrypted => {
+                f.pad("the module is already signed (`~SCE` header) and can not be repacked")
+            },
+            Error::NotMips(e_machine) => write!(
+                f,
+                "the elf is not for MIPS (e_machine is {e_machine}), the PSP CPU is MIPS (8)"
+            ),
+            Error::SegmentAlign(align) => write!(
+                f,
+                "segment alignment 0x{align:X} does not fit in the 16-bit alignment of the PSP \
+                 header"
+            ),
+            Error::SelfCheck => {
+                f.pad("self-check failed: the packed payload does not decompress to the input ELF")
+            },
+            Error::MalformedPbp { section, offset } => write!(
+                f,
+                "malformed PBP: the {section} offset 0x{offset:08X} is before the previous \
+                 sub-file or past the end of the file"
+            ),
+            Error::HeadersDiffer => f.pad("the PSP headers differ"),
+            Error::PadAlign(align) => write!(f, "padding alignment {align} is not a power of two"),
+            Error::SizeOverflow { field, size } => {
+                write!(f, "the {field} of {size} B does not fit in the 32-bit field of the header")
+            },
+            Error::TooManySegments(count) => write!(
+                f,
+                "the elf part of the file has {count} loadable segments, the PSP header holds at \
+                 most 4"
+            ),
+            Error::UnknownAttribute(bits) => {
+                let listed: Vec<_> = (0..16)
+                    .map(|bit| 1u16 << bit)
+                    .filter(|bit| bits & bit != 0)
+                    .map(|bit| format!("0x{bit:04X}"))
+                    .collect();
+                write!(
+                    f,
+                    "the module info attribute has unknown bits 0x{bits:04X} ({})",
+                    listed.join(", ")
+                )
+            },
+            Error::UnknownCompAttribute(attribute) => write!(
+                f,
+                "unknown compression attribute 0x{attribute:04X}, the known ones are 0x0000 \
+                 (stored) and 0x0001 (gzip)"
+            ),
+            Error::BadLibraryTable(addr) => write!(
+                f,
+                "the library table at 0x{addr:08X} is malformed or points outside the elf segments"
+            ),
+            Error::WrongElfType(e_type) => {
+                let (kind, fix) = match *e_type {
+                    1 => ("an unlinked object file (ET_REL)", "link it, then run `psp-prxgen`"),
+                    2 => ("a static executable (ET_EXEC)", "run `psp-prxgen` on it"),
+                    _ => ("not a PRX", "run `psp-prxgen` on it"),
+                };
+                write!(
+                    f,
+                    "the elf is {kind}, but a PRX (e_type 0xFFA0) is required: {fix}, or set \
+                     BUILD_PRX = 1 in the PSPSDK makefile"
+                )
+            },
+            Error::EmptyFile => f.pad("the file is empty"),
+            Error::UncoveredRelocs(offset) => write!(
+                f,
+                "the PRX_RELOC section at 0x{offset:08X} is not covered by a relocation segment, \
+                 stripping would drop it"
+            ),
         }
     }
 }
@@ -95,10 +319,12 @@ impl fmt::Debug for Error {
             Self::FromBytes {
                 input_len,
                 expected_len,
+                context,
             } => f
                 .debug_struct("FromBytes")
                 .field("input_len", input_len)
                 .field("expected_len", expected_len)
+                .field("context", context)
                 .finish(),
             Self::FileTooSmall => write!(f, "FileTooSmall"),
             Self::FileTooBig => write!(f, "FileTooBig"),
@@ -111,12 +337,84 @@ impl fmt::Debug for Error {
             Self::BssNotFound => write!(f, "BssNotFound"),
             Self::NotPbp => write!(f, "NotPbp"),
             Self::Io(arg0) => f.debug_tuple("Io").field(arg0).finish(),
-            Self::Alignment { align, addr } => f
+            Self::Alignment {
+                align,
+                addr,
+                context,
+            } => f
                 .debug_struct("Alignment")
                 .field("align", align)
                 .field("addr", &format_args!("{addr:#08X}"))
+                .field("context", context)
+                .finish(),
+            Self::CStr { error, context } => f
+                .debug_struct("CStr")
+                .field("error", error)
+                .field("context", context)
+                .finish(),
+            Self::NotSfo => write!(f, "NotSfo"),
+            Self::SfoTruncated(table) => f.debug_tuple("SfoTruncated").field(table).finish(),
+            Self::NotPacked => write!(f, "NotPacked"),
+            Self::Corrupted(check) => f.debug_tuple("Corrupted").field(check).finish(),
+            Self::UnknownTags { tag, oe_tag } => f
+                .debug_struct("UnknownTags")
+                .field("tag", &format_args!("0x{tag:08X}"))
+                .field("oe_tag", &format_args!("0x{oe_tag:08X}"))
+                .finish(),
+            Self::Not32Bit => write!(f, "Not32Bit"),
+            Self::NotLittleEndian(e_data) => {
+                f.debug_tuple("NotLittleEndian").field(e_data).finish()
+            },
+            Self::Encrypted => write!(f, "Encrypted"),
+            Self::NotMips(e_machine) => f.debug_tuple("NotMips").field(e_machine).finish(),
+            Self::SegmentAlign(align) => {
+                f.debug_tuple("SegmentAlign").field(&format_args!("0x{align:X}")).finish()
+            },
+            Self::SelfCheck => write!(f, "SelfCheck"),
+            Self::MalformedPbp { section, offset } => f
+                .debug_struct("MalformedPbp")
+                .field("section", section)
+                .field("offset", &format_args!("0x{offset:08X}"))
                 .finish(),
-            Self::CStr(e) => f.debug_tuple("CStr").field(e).finish(),
+            Self::HeadersDiffer => write!(f, "HeadersDiffer"),
+            Self::PadAlign(align) => f.debug_tuple("PadAlign").field(align).finish(),
+            Self::SizeOverflow { field, size } => f
+                .debug_struct("SizeOverflow")
+                .field("field", field)
+                .field("size", size)
+                .finish(),
+            Self::TooManySegments(count) => f.debug_tuple("TooManySegments").field(count).finish(),
+            Self::UnknownAttribute(bits) => f
+                .debug_tuple("UnknownAttribute")
+                .field(&format_args!("0x{bits:04X}"))
+                .finish(),
+            Self::UnknownCompAttribute(attribute) => f
+                .debug_tuple("UnknownCompAttribute")
+                .field(&format_args!("0x{attribute:04X}"))
+                .finish(),
+            Self::BadLibraryTable(addr) => f
+                .debug_tuple("BadLibraryTable")
+                .field(&format_args!("0x{addr:08X}"))
+                .finish(),
+            Self::WrongElfType(e_type) => f
+                .debug_tuple("WrongElfType")
+                .field(&format_args!("0x{e_type:04X}"))
+                .finish(),
+            Self::EmptyFile => write!(f, "EmptyFile"),
+            Self::UncoveredRelocs(offset) => f
+                .debug_tuple("UncoveredRelocs")
+                .field(&format_args!("0x{offset:08X}"))
+                .finish(),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            Error::CStr { error, .. } => Some(error),
+            _ => None,
         }
     }
 }
@@ -0,0 +1,32 @@
+//! Library to compress PRX and PBP PlayStation Portable file formats.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use psp_packer::UnkPspExecutable;
+//!
+//! # fn main() -> Result<(), psp_packer::Error> {
+//! let file = UnkPspExecutable::from_path("EBOOT.PBP".as_ref())?;
+//! let compressed = file.compress()?;
+//! std::fs::write("EBOOT_PACKED.PBP", compressed.as_bytes())?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod elf;
+mod error;
+mod psp;
+mod sfo;
+mod utils;
+
+pub use crate::{
+    elf::{Elf32Ehdr, Elf32Phdr, Elf32Shdr},
+    error::Error,
+    psp::{
+        detect_kind, validate_size, validate_tags, BssSource, CompAttribute, CompPspExecutable,
+        DecryptMode, ElfSection, ExecutableInfo, ExecutableKind, ModInfoAttribute, ModuleLibrary,
+        ModuleSymbols, PackOptions, PackStats, PackTimings, PbpBuilder, PbpHeader, PbpSection,
+        PspHeader, SceModuleInfo, UnkPspExecutable, VerifyCheck, KNOWN_TAGS,
+    },
+    sfo::{SfoTable, SfoValue},
+};
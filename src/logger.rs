@@ -0,0 +1,57 @@
+//! Logger of the `logging` feature, writing the records to stderr.
+//!
+//! The application diagnostics are logged at the `info` (`--verbose`) and `warn` levels, the
+//! library logs the packing steps at the `debug` and `trace` levels.
+
+use std::fmt::Write;
+
+use log::{
+    kv::{self, Key, Value, VisitSource},
+    Level, LevelFilter, Log, Metadata, Record,
+};
+
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let prefix = match record.level() {
+            Level::Info => String::new(),
+            Level::Warn => "WARNING: ".to_owned(),
+            Level::Error => "ERROR: ".to_owned(),
+            level => format!("{level}: {}: ", record.target()),
+        };
+        let mut line = format!("psp-packer: {prefix}{}", record.args());
+        let _ = record.key_values().visit(&mut Fields(&mut line));
+
+        eprintln!("{line}");
+    }
+
+    fn flush(&self) {}
+}
+
+/// Appends the key-values of a record as ` key=value`.
+struct Fields<'a>(&'a mut String);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let _ = write!(self.0, " {key}={value}");
+        Ok(())
+    }
+}
+
+/// Install the stderr logger, logging the records up to `level`.
+pub(crate) fn init(level: LevelFilter) {
+    static LOGGER: StderrLogger = StderrLogger;
+
+    // Only fails if a logger is already installed, which is never the case
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level);
+}
@@ -1,15 +1,49 @@
 use std::{
+    borrow::Cow,
+    env,
+    ffi::CStr,
     fs::{self},
-    path::PathBuf,
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
 };
 
-use crate::{error::Error, psp::UnkPspExecutable};
+use clap::{
+    builder::styling::{AnsiColor, Style},
+    error::ErrorKind,
+    parser::ValueSource,
+    ArgMatches, Command,
+};
+use clap_complete::Shell;
+use psp_packer::{
+    validate_tags, BssSource, CompAttribute, DecryptMode, Elf32Phdr, ElfSection, Error,
+    ExecutableInfo, ExecutableKind, ModuleSymbols, PackOptions, PackStats, PackTimings, PbpBuilder,
+    PbpSection, PspHeader, SfoTable, UnkPspExecutable, VerifyCheck, KNOWN_TAGS,
+};
 
 mod cli;
-mod elf;
-mod error;
-mod psp;
-mod utils;
+mod config;
+#[cfg(feature = "logging")]
+mod logger;
+
+/// Print a message of `--verbose` to stderr, through the logger with the `logging` feature.
+macro_rules! note {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "logging")]
+        log::info!($($arg)+);
+        #[cfg(not(feature = "logging"))]
+        eprintln!("psp-packer: {}", format_args!($($arg)+));
+    }};
+}
+
+/// Print a warning to stderr, through the logger with the `logging` feature.
+macro_rules! warning {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "logging")]
+        log::warn!($($arg)+);
+        #[cfg(not(feature = "logging"))]
+        eprintln!("psp-packer: WARNING: {}", format_args!($($arg)+));
+    }};
+}
 
 fn main() {
     let res = exec();
@@ -21,55 +55,1213 @@ fn main() {
 }
 
 fn exec() -> Result<(), Error> {
-    let cmd = cli::create_app();
-    let matches = cmd.get_matches();
+    let mut cmd = cli::create_app();
+    let matches = cmd.get_matches_mut();
+
+    #[cfg(feature = "logging")]
+    logger::init(log_level(&matches));
+
+    if matches.get_flag("list-error-codes") {
+        for error in Error::variants() {
+            println!("{}\t{}\t{error}", error.error_code(), error.name());
+        }
+        return Ok(());
+    }
 
-    // Ok to unwrap as it is required.
-    let file_name = matches.get_one::<PathBuf>("FILE").unwrap();
+    if matches.get_flag("list-tags") {
+        for (psp_tag, oe_tag, kind, description) in KNOWN_TAGS {
+            println!("0x{psp_tag:08X}\t0x{oe_tag:08X}\t{kind}\t{description}");
+        }
+        return Ok(());
+    }
+
+    // The top level arguments are the ones of `pack`, only the global ones go with a subcommand
+    if let Some((name, _)) = matches.subcommand() {
+        let pack_arg = matches.ids().find(|id| {
+            !["verbose", "quiet", "log-level"].contains(&id.as_str())
+                && matches.value_source(id.as_str()) == Some(ValueSource::CommandLine)
+        });
+        if let Some(id) = pack_arg {
+            cmd.error(
+                ErrorKind::ArgumentConflict,
+                format!("the argument `{id}` cannot be used before the `{name}` subcommand"),
+            )
+            .exit();
+        }
+    }
+
+    match matches.subcommand() {
+        Some(("pack", matches)) => pack_command(&mut cmd, matches),
+        Some(("unpack", matches)) => unpack_command(&mut cmd, matches),
+        Some(("info", matches)) => {
+            let file = open_input(file_path(matches))?;
+            let palette = Palette::new(matches);
+            let list_segments = matches.get_flag("list-segments");
+            let list_sections = matches.get_flag("list-sections");
+            let symbols = matches.get_flag("symbols");
+            if !list_segments && !list_sections && !symbols {
+                let info = file.probe()?;
+                print_info(&info, file.sfo()?.as_ref(), file.pbp_sections()?.as_deref(), palette);
+                return Ok(());
+            }
+
+            // The ELF details are listed even if probing fails, to find out why
+            let mut packed = false;
+            match file.probe() {
+                Ok(info) => {
+                    packed = info.is_packed();
+                    print_info(
+                        &info,
+                        file.sfo()?.as_ref(),
+                        file.pbp_sections()?.as_deref(),
+                        palette,
+                    )
+                },
+                Err(e) => warning!("{e}"),
+            }
+            let unpacked;
+            let elf = if packed {
+                unpacked = file.unpack()?;
+                &unpacked
+            } else {
+                &file
+            };
+            if list_segments {
+                println!();
+                print_segments(&elf.program_headers()?);
+            }
+            if list_sections {
+                println!();
+                print_sections(&elf.section_headers()?);
+            }
+            if symbols {
+                println!();
+                print_symbols(&elf.symbols()?);
+            }
+            Ok(())
+        },
+        Some(("verify", matches)) => {
+            let file = open_input(file_path(matches))?;
+            for check in VerifyCheck::ALL {
+                if let Err(e) = file.verify_check(check) {
+                    println!("FAIL  {check}");
+                    return Err(e);
+                }
+                println!("PASS  {check}");
+            }
+            Ok(())
+        },
+        Some(("compare", matches)) => {
+            // Ok to unwrap as it is required
+            let other = matches.get_one::<PathBuf>("OTHER").unwrap();
+            compare(file_path(matches), other, matches.get_flag("include-keys"))
+        },
+        Some(("completions", matches)) => {
+            // Ok to unwrap as it is required
+            let shell = *matches.get_one::<Shell>("SHELL").unwrap();
+            let bin_name = cmd.get_name().to_owned();
+            clap_complete::generate(shell, &mut cmd, bin_name, &mut io::stdout());
+            Ok(())
+        },
+        Some(("man", _)) => {
+            clap_mangen::Man::new(cmd).render(&mut io::stdout())?;
+            Ok(())
+        },
+        Some(("extract", matches)) => {
+            // Ok to unwrap as it is required
+            let out_dir = matches.get_one::<PathBuf>("DIR").unwrap();
+            extract(file_path(matches), out_dir, is_verbose(matches))
+        },
+        Some(("make-pbp", matches)) => {
+            let output_file = matches
+                .get_one::<PathBuf>("output")
+                .map_or(Path::new("EBOOT.PBP"), PathBuf::as_path);
+            let dry_run = matches.get_flag("dry-run");
+            make_pbp(matches, file_path(matches), output_file, dry_run, is_verbose(matches))
+        },
+        // Without a subcommand, the arguments are the ones of `pack`
+        _ => pack_command(&mut cmd, &matches),
+    }
+}
+
+/// Whether to report what is done, with `--verbose` or, with the `logging` feature, a log level of
+/// at least `info`.
+fn is_verbose(matches: &ArgMatches) -> bool {
+    #[cfg(feature = "logging")]
+    if log::log_enabled!(log::Level::Info) {
+        return true;
+    }
+    matches.get_flag("verbose")
+}
+
+/// The log level of `--log-level`, then `RUST_LOG`, then implied by `--verbose` and `--quiet`.
+#[cfg(feature = "logging")]
+fn log_level(matches: &ArgMatches) -> log::LevelFilter {
+    // Ok to unwrap as the possible values are the level names
+    if let Some(level) = matches.get_one::<String>("log-level") {
+        return level.parse().unwrap();
+    }
+    if let Some(level) = env::var("RUST_LOG").ok().and_then(|level| level.parse().ok()) {
+        return level;
+    }
+
+    if matches.get_flag("verbose") {
+        log::LevelFilter::Info
+    } else if matches.get_flag("quiet") {
+        log::LevelFilter::Error
+    } else {
+        log::LevelFilter::Warn
+    }
+}
+
+/// Returns the `<FILE>` argument.
+fn file_path(matches: &ArgMatches) -> &Path {
+    // Ok to unwrap as it is required unless listing error codes or tags, handled before.
+    matches.get_one::<PathBuf>("FILE").unwrap()
+}
+
+/// Reject reading `<FILE>` from the standard input without an `output` option, as there is no
+/// file to write the result next to, unless nothing is written.
+fn check_stdio_output(cmd: &mut Command, matches: &ArgMatches, dry_run: bool) {
     let output_file = matches.get_one::<PathBuf>("output");
+    if is_stdio(file_path(matches)) && output_file.is_none() && !dry_run {
+        cmd.error(
+            ErrorKind::MissingRequiredArgument,
+            "the `output` option is required when reading <FILE> from the standard input",
+        )
+        .exit();
+    }
+}
 
-    let dry_run = matches.get_flag("dry-run");
-    let verbose = matches.get_flag("verbose");
+fn unpack_command(cmd: &mut Command, matches: &ArgMatches) -> Result<(), Error> {
+    check_stdio_output(cmd, matches, matches.get_flag("dry-run"));
+
+    let file_name = file_path(matches);
+    let elf = open_input(file_name)?.unpack()?;
+    let output_file = match matches.get_one::<PathBuf>("output") {
+        Some(output_file) => output_file.clone(),
+        None if matches.get_flag("in-place") => file_name.to_owned(),
+        None => default_output_path(file_name, elf.detect_kind()?, "unpacked"),
+    };
+    let permissions = input_permissions(file_name)?;
+
+    if matches.get_flag("dry-run") {
+        if is_verbose(matches) {
+            warning!("not writing to file due to dry run");
+        }
+    } else {
+        write_output(&output_file, elf.as_bytes(), permissions)?;
+    }
+
+    if is_verbose(matches) {
+        note!("Unpacked `{}` ({} B)", output_file.display(), elf.size());
+    }
+
+    Ok(())
+}
+
+fn pack_command(cmd: &mut Command, matches: &ArgMatches) -> Result<(), Error> {
+    let file_name = file_path(matches);
+    let output_dir = matches.get_one::<PathBuf>("output-dir");
+    if output_dir.is_some() && is_stdio(file_name) {
+        cmd.error(
+            ErrorKind::ArgumentConflict,
+            "the `output-dir` option cannot be used when reading <FILE> from the standard input",
+        )
+        .exit();
+    }
+
+    let output_file = matches.get_one::<PathBuf>("output");
+    // Nothing is written either with `stats-only`, only the statistics are printed
+    let stats_only = matches.get_flag("stats-only");
+    let dry_run = matches.get_flag("dry-run") || stats_only;
+    check_stdio_output(cmd, matches, dry_run);
+    let verbose = is_verbose(matches);
+
+    let (config_file, config) = match config::Config::load() {
+        Ok(config) => config,
+        Err(e) => cmd
+            .error(ErrorKind::InvalidValue, format!("invalid config file {e}"))
+            .exit(),
+    };
+    if let Some(config_file) = config_file.filter(|_| verbose) {
+        note!("Using the config file `{}`", config_file.display());
+    }
+
+    // Ok to unwrap as the possible values are restricted
+    let preset = matches
+        .get_one::<String>("preset")
+        .map(|preset| config::preset_kind(preset).unwrap());
+
+    let strict = matches.get_flag("strict");
+    let quiet = matches.get_flag("quiet");
+    let json = matches.get_flag("json");
+
+    if json && output_file.is_some_and(|output_file| is_stdio(output_file)) {
+        cmd.error(
+            ErrorKind::ArgumentConflict,
+            "the `json` option cannot be used when writing the packed file to the standard output",
+        )
+        .exit();
+    }
 
     let tags = matches
         .get_many::<u32>("tags")
         .and_then(|mut tags| tags.next().copied().zip(tags.next().copied()));
 
-    let file = UnkPspExecutable::from_path(file_name)?;
-    let og_file_size = file.size();
-    let compressed = match tags {
-        Some((psp_tag, oe_tag)) => file.compress_with_tags(psp_tag, oe_tag)?,
-        None => file.compress()?,
+    let header = match matches.get_one::<PathBuf>("header") {
+        Some(path) => Some(PspHeader::from_header_bytes(&fs::read(path)?)?),
+        None => None,
     };
 
-    if dry_run {
-        if verbose {
-            eprintln!("psp-packer: WARNING: not writing to file due to dry run");
+    // Without tags or a preset on the command line, the tags come from the imported header, then
+    // `PSP_PACKER_TAGS`, then the config file, and the default tags of the executable kind
+    // otherwise
+    let (tags, preset) = if tags.is_some() || preset.is_some() {
+        (tags, preset)
+    } else if let Some(header) = &header {
+        (Some((header.tag, header.oe_tag)), None)
+    } else {
+        match env::var("PSP_PACKER_TAGS") {
+            Ok(env_tags) => match cli::parse_tags(&env_tags) {
+                Ok(tags) => (Some(tags), None),
+                Err(e) => cmd
+                    .error(ErrorKind::InvalidValue, format!("invalid `PSP_PACKER_TAGS`: {e}"))
+                    .exit(),
+            },
+            Err(_) => (config.tags, config.preset),
         }
+    };
+
+    if let Some((psp_tag, oe_tag)) = tags {
+        match validate_tags(psp_tag, oe_tag) {
+            Err(e) if strict => return Err(e),
+            Err(e) if !quiet => warning!("{e}"),
+            Err(_) => {},
+            Ok(()) => {},
+        }
+    }
+
+    let tags = tags.or_else(|| preset.map(|kind| kind.default_tags()));
+
+    // Ok to unwrap as it has a default value.
+    let level = match (matches.value_source("level"), config.level) {
+        (Some(ValueSource::CommandLine), _) | (_, None) => {
+            *matches.get_one::<u32>("level").unwrap()
+        },
+        (_, Some(level)) => level,
+    };
+
+    let mut options = PackOptions::new().level(level);
+    if let Some((psp_tag, oe_tag)) = tags {
+        options = options.tags(psp_tag, oe_tag);
+    }
+    if let Some(&devkit_version) = matches.get_one::<u32>("devkit-version") {
+        options = options.devkit_version(devkit_version);
+    }
+    if let Some(&comp_attribute) = matches.get_one::<u16>("comp-attribute") {
+        if CompAttribute::try_from(comp_attribute).is_err() {
+            let e = Error::UnknownCompAttribute(comp_attribute);
+            if strict {
+                return Err(e);
+            } else if !quiet {
+                warning!("{e}");
+            }
+        }
+        options = options.comp_attribute(comp_attribute);
+    }
+    if let Some(mode) = matches.get_one::<String>("decrypt-mode") {
+        // Ok to unwrap as the possible values are the decrypt mode names.
+        options = options.decrypt_mode(DecryptMode::from_name(mode).unwrap());
+    }
+    if let Some(&(major, minor)) = matches.get_one::<(u8, u8)>("module-version") {
+        options = options.module_version(major, minor);
+    }
+    if let Some(name) = matches.get_one::<String>("name") {
+        options = options.name(name);
+    }
+    if let Some(&seed) = matches.get_one::<u64>("seed") {
+        options = options.seed(seed);
+    }
+    if let Some(header) = &header {
+        options = options.key_data(header).scheck(header.scheck);
+    }
+    if let Some(kind) = matches.get_one::<String>("kind") {
+        // Ok to unwrap as the possible values are restricted
+        options = options.kind(config::preset_kind(kind).unwrap());
+    }
+    if matches.get_flag("self-check") {
+        options = options.self_check(true);
+    }
+    if matches.get_flag("overlap") {
+        options = options.overlap(true);
+    }
+    if let Some(&align) = matches.get_one::<u32>("pad-to") {
+        options = options.pad_to(align);
+    }
+    if matches.get_flag("strip") {
+        options = options.strip(true);
+    }
+    if matches.get_flag("no-random") {
+        options = options.no_random(true);
+    }
+    if matches.get_flag("allow-no-bss") {
+        options = options.allow_no_bss(true);
+    }
+    if let Some(bss_from) = matches.get_one::<String>("bss-from") {
+        // Ok to unwrap as the possible values are restricted
+        options = options.bss_from(BssSource::from_name(bss_from).unwrap());
+    }
+    let force = matches.get_flag("force");
+    if force {
+        options = options.force(true);
+    }
+    if matches.get_flag("preserve-reserved") {
+        options = options.preserve_reserved(true);
+    }
+    if verbose {
+        options = options.timings(true);
+    }
+    if matches.get_flag("progress") && !quiet && io::stderr().is_terminal() {
+        options = options.progress(print_progress);
+    }
+
+    let settings = PackSettings {
+        options,
+        dry_run,
+        verbose,
+        json,
+        stats_only,
+        in_place: matches.get_flag("in-place"),
+        backup: matches.get_flag("backup"),
+        output_dir: output_dir.cloned(),
+        dump_header: matches.get_one::<PathBuf>("dump-header").cloned(),
+        strict,
+        quiet,
+        #[cfg(feature = "rayon")]
+        jobs: matches.get_one::<usize>("jobs").copied(),
+    };
+
+    if matches.get_flag("recursive") && file_name.is_dir() {
+        return pack_dir(file_name, &settings, force);
+    }
+
+    // Ok to unwrap as `FILE` is a file here, not `-` nor a directory
+    let output_file = output_file
+        .cloned()
+        .or_else(|| output_dir.map(|dir| dir.join(file_name.file_name().unwrap())));
+    let report = pack(file_name, output_file.as_deref(), &settings)?;
+    if json {
+        print_json(file_name, &report);
+    } else if stats_only {
+        print_stats(file_name, &report.stats);
+    } else if verbose {
+        print_report(file_name, &report);
+    }
+
+    Ok(())
+}
+
+/// Render the compression progress on the current stderr line.
+fn print_progress(done: usize, total: usize) {
+    let percent = (done * 100).checked_div(total).unwrap_or(100);
+    eprint!("\rpsp-packer: Compressing... {percent:>3}%");
+    if done >= total {
+        eprintln!();
+    }
+}
+
+/// Settings shared by every packed file.
+struct PackSettings {
+    options: PackOptions,
+    dry_run: bool,
+    verbose: bool,
+    json: bool,
+    /// Print the statistics of each file, which is not written.
+    stats_only: bool,
+    /// Overwrite the input files, instead of writing the packed files next to them.
+    in_place: bool,
+    backup: bool,
+    /// Directory where the packed files are written, instead of next to the input files.
+    output_dir: Option<PathBuf>,
+    /// File where the PSP header of the packed file is written.
+    dump_header: Option<PathBuf>,
+    strict: bool,
+    quiet: bool,
+    /// Number of files packed in parallel, all cores if not set.
+    #[cfg(feature = "rayon")]
+    jobs: Option<usize>,
+}
+
+/// What packing a file did, reported under `verbose` once it is done.
+struct PackReport {
+    stats: PackStats,
+    tags: (u32, u32),
+    elf_crc32: u32,
+    comp_attribute: u16,
+    timings: Option<PackTimings>,
+    written: Written,
+}
+
+/// Where the packed file was written.
+enum Written {
+    DryRun,
+    Output,
+    /// Next to the input, as named by [`default_output_path`].
+    Derived(PathBuf),
+    Overwritten {
+        backup: Option<PathBuf>,
+    },
+}
+
+fn pack(
+    file_name: &Path, output_file: Option<&Path>, settings: &PackSettings,
+) -> Result<PackReport, Error> {
+    let PackSettings {
+        options,
+        dry_run,
+        in_place,
+        backup,
+        output_dir,
+        dump_header,
+        strict,
+        quiet,
+        verbose,
+        ..
+    } = settings;
+
+    let file = open_input(file_name)?;
+    match file.validate_machine() {
+        Err(e) if *strict => return Err(e),
+        Err(e) if !quiet => warning!("`{}`: {e}", file_name.display()),
+        Err(_) => {},
+        Ok(()) => {},
+    }
+    match file.validate_attribute() {
+        Err(e) if *strict => return Err(e),
+        Err(e) if *verbose => warning!("`{}`: {e}", file_name.display()),
+        Err(_) => {},
+        Ok(()) => {},
+    }
+    let compressed = file.compress_with(options)?;
+    let permissions = input_permissions(file_name)?;
+
+    if let Some(dump_header) = dump_header {
+        write_output(dump_header, compressed.header_bytes(), None)?;
+    }
+
+    let written = if *dry_run {
+        Written::DryRun
     } else if let Some(output_file) = output_file {
-        fs::write(output_file, compressed.as_bytes())?;
+        if let Some(parent) = output_file.parent().filter(|_| output_dir.is_some()) {
+            fs::create_dir_all(parent)?;
+        }
+        write_output(output_file, compressed.as_bytes(), permissions)?;
+        Written::Output
+    } else if !in_place {
+        let output_file = default_output_path(file_name, compressed.kind(), "packed");
+        write_output(&output_file, compressed.as_bytes(), permissions)?;
+        Written::Derived(output_file)
     } else {
+        let backup = if *backup {
+            let backup_file = backup_path(file_name);
+            if let Err(e) = fs::copy(file_name, &backup_file) {
+                warning!("failed to back up `{}`, leaving it as is", file_name.display());
+                return Err(e.into());
+            }
+            Some(backup_file)
+        } else {
+            None
+        };
+        write_output(file_name, compressed.as_bytes(), permissions)?;
+        Written::Overwritten { backup }
+    };
+
+    Ok(PackReport {
+        stats: compressed.stats(),
+        tags: (compressed.header().tag, compressed.header().oe_tag),
+        elf_crc32: compressed.elf_crc32(),
+        comp_attribute: compressed.header().comp_attribute,
+        timings: compressed.timings(),
+        written,
+    })
+}
+
+fn print_report(file_name: &Path, report: &PackReport) {
+    match &report.written {
+        Written::DryRun => warning!("not writing to file due to dry run"),
+        Written::Output => {},
+        Written::Derived(output_file) => note!("Writing to `{}`", output_file.display()),
+        Written::Overwritten {
+            backup: Some(backup_file),
+        } => {
+            note!("Backed up the original to `{}`", backup_file.display());
+        },
+        Written::Overwritten { backup: None } => {
+            warning!("overwriting `{}` in place", file_name.display())
+        },
+    }
+
+    let stats = &report.stats;
+    note!("The file is a {}", stats.kind);
+    note!(
+        "Original file size: {:.2} KiB ({} B)",
+        stats.original_size as f64 / 1024.0,
+        stats.original_size
+    );
+    note!(
+        "Compressed file size: {:.2} KiB ({} B)",
+        stats.compressed_size as f64 / 1024.0,
+        stats.compressed_size
+    );
+    // Stored ELFs only grow by the size of the header, shown as not compressed at all
+    let saved = stats.original_size.saturating_sub(stats.compressed_size);
+    note!(
+        "Compression ratio: {:.1}% (saved {:.1} KiB)",
+        stats.ratio.min(1.0) * 100.0,
+        saved as f64 / 1024.0
+    );
+    if let Some(stripped_size) = stats.stripped_size {
+        note!("Stripped from the ELF: {stripped_size} B");
+    }
+    let (psp_tag, oe_tag) = report.tags;
+    note!("Tags: 0x{psp_tag:08X} 0x{oe_tag:08X}");
+    note!("Decrypt mode: {}", stats.decrypt_mode);
+    note!("Devkit version: 0x{:08X}", stats.devkit_version);
+    match CompAttribute::try_from(report.comp_attribute) {
+        Ok(method) => note!("Compression attribute: 0x{:04X} ({method})", report.comp_attribute),
+        Err(_) => note!("Compression attribute: 0x{:04X} (unknown)", report.comp_attribute),
+    }
+    note!("ELF CRC32: 0x{:08X}", report.elf_crc32);
+    if let Some(timings) = report.timings {
+        note!(
+            "Time: parse {:.2?}, compress {:.2?}, write {:.2?}",
+            timings.parse,
+            timings.compress,
+            timings.write
+        );
+    }
+}
+
+/// Print the sizes and ratio of the packed file on a single line to stdout.
+fn print_stats(file_name: &Path, stats: &PackStats) {
+    println!(
+        "{}: {} B -> {} B ({:.1}%)",
+        file_name.display(),
+        stats.original_size,
+        stats.compressed_size,
+        stats.ratio * 100.0
+    );
+}
+
+/// Print the report as a single line JSON object to stdout.
+fn print_json(file_name: &Path, report: &PackReport) {
+    let stats = &report.stats;
+    let (psp_tag, oe_tag) = report.tags;
+    println!(
+        "{{\"file\":{},\"original_size\":{},\"compressed_size\":{},\"ratio\":{:.4},\"kind\":{},\"\
+         decrypt_mode\":{},\"devkit_version\":\"0x{:08X}\",\"tags\":[\"0x{psp_tag:08X}\",\"\
+         0x{oe_tag:08X}\"]}}",
+        json_string(&file_name.to_string_lossy()),
+        stats.original_size,
+        stats.compressed_size,
+        stats.ratio,
+        json_string(&stats.kind.to_string()),
+        json_string(stats.decrypt_mode.name()),
+        stats.devkit_version,
+    );
+}
+
+/// Quote and escape `s` as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Pack every PRX and PBP under `dir`, skipping already packed files.
+///
+/// The results are reported in the order of the files, even when packed in parallel.
+fn pack_dir(dir: &Path, settings: &PackSettings, force: bool) -> Result<(), Error> {
+    let files = find_packable_files(dir, force)?;
+    let results = pack_all(dir, &files, settings)?;
+
+    for (path, result) in files.iter().zip(results) {
+        match result {
+            Err(Error::AlreadyPacked) => {
+                if settings.verbose {
+                    note!("Skipped `{}`: already packed", path.display());
+                }
+            },
+            Err(e) => {
+                warning!("failed to pack `{}`", path.display());
+                return Err(e);
+            },
+            Ok(report) => {
+                if settings.json {
+                    print_json(path, &report);
+                } else if settings.stats_only {
+                    print_stats(path, &report.stats);
+                } else if settings.verbose {
+                    print_report(path, &report);
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Pack every file under `dir`, next to it or in the output directory, in parallel.
+///
+/// With a single job, the files are packed one after the other on the current thread.
+#[cfg(feature = "rayon")]
+fn pack_all(
+    dir: &Path, files: &[PathBuf], settings: &PackSettings,
+) -> Result<Vec<Result<PackReport, Error>>, Error> {
+    use rayon::prelude::*;
+
+    let pack_one = |path: &PathBuf| pack_file(dir, path, settings);
+
+    match settings.jobs {
+        Some(1) => Ok(files.iter().map(pack_one).collect()),
+        Some(jobs) => {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(jobs)
+                .build()
+                .map_err(io::Error::other)?;
+            Ok(pool.install(|| files.par_iter().map(pack_one).collect()))
+        },
+        None => Ok(files.par_iter().map(pack_one).collect()),
+    }
+}
+
+/// Pack every file under `dir`, next to it or in the output directory.
+#[cfg(not(feature = "rayon"))]
+fn pack_all(
+    dir: &Path, files: &[PathBuf], settings: &PackSettings,
+) -> Result<Vec<Result<PackReport, Error>>, Error> {
+    Ok(files.iter().map(|path| pack_file(dir, path, settings)).collect())
+}
+
+/// Pack `path`, found under `dir`, announcing it first with `--verbose`.
+fn pack_file(dir: &Path, path: &Path, settings: &PackSettings) -> Result<PackReport, Error> {
+    if settings.verbose {
+        note!("Packing `{}`", path.display());
+    }
+    pack(path, output_path(dir, path, settings).as_deref(), settings)
+}
+
+/// Returns the path of `file` under the output directory, relative to `dir`, or `None` to write
+/// it to its default path, or in place.
+fn output_path(dir: &Path, file: &Path, settings: &PackSettings) -> Option<PathBuf> {
+    let output_dir = settings.output_dir.as_ref()?;
+    // Ok to unwrap as the files are found under `dir`
+    Some(output_dir.join(file.strip_prefix(dir).unwrap()))
+}
+
+/// Returns the path next to `file_name` where its packed or unpacked file is written by default:
+/// `<stem>_<suffix>.<ext>`, or `<STEM>_<SUFFIX>.<EXT>` for an uppercase name like `EBOOT.PBP`.
+///
+/// Without an extension, the one of the executable kind is added, `.prx` or `.PBP`.
+fn default_output_path(file_name: &Path, kind: ExecutableKind, suffix: &str) -> PathBuf {
+    let stem = file_name.file_stem().unwrap_or_default();
+    let is_uppercase = !stem.to_string_lossy().chars().any(char::is_lowercase);
+
+    let mut name = stem.to_owned();
+    if is_uppercase {
+        name.push(format!("_{}.", suffix.to_uppercase()));
+    } else {
+        name.push(format!("_{suffix}."));
+    }
+    match file_name.extension() {
+        Some(extension) => name.push(extension),
+        None if kind.is_pbp() => name.push("PBP"),
+        None => name.push("prx"),
+    }
+    file_name.with_file_name(name)
+}
+
+/// Returns `<file>.bak`, or `<file>.bak.N` with the first free `N` if it already exists.
+fn backup_path(file_name: &Path) -> PathBuf {
+    let mut backup = file_name.as_os_str().to_owned();
+    backup.push(".bak");
+
+    let mut path = PathBuf::from(&backup);
+    for n in 1.. {
+        if !path.exists() {
+            break;
+        }
+        let mut numbered = backup.clone();
+        numbered.push(format!(".{n}"));
+        path = numbered.into();
+    }
+
+    path
+}
+
+/// Recursively find the files under `dir` that start with an ELF or PBP magic, or a packed PRX
+/// magic if `include_packed` is set.
+///
+/// Symbolic links are not followed.
+fn find_packable_files(dir: &Path, include_packed: bool) -> Result<Vec<PathBuf>, Error> {
+    const MAGICS: [&[u8; 4]; 2] = [b"\x7FELF", b"\0PBP"];
+    const PACKED_MAGIC: &[u8; 4] = b"~PSP";
+
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+
+        if file_type.is_dir() {
+            files.extend(find_packable_files(&path, include_packed)?);
+        } else if file_type.is_file() {
+            let mut magic = [0u8; 4];
+            let is_packable = fs::File::open(&path)?.read_exact(&mut magic).is_ok_and(|_| {
+                MAGICS.contains(&&magic) || (include_packed && &magic == PACKED_MAGIC)
+            });
+
+            if is_packable {
+                files.push(path);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Whether the file name refers to the standard input/output.
+fn is_stdio(file_name: &Path) -> bool {
+    file_name == Path::new("-")
+}
+
+/// Read the input file, or the standard input if the file name is `-`.
+fn open_input(file_name: &Path) -> Result<UnkPspExecutable, Error> {
+    if is_stdio(file_name) {
+        UnkPspExecutable::from_reader(io::stdin().lock())
+    } else {
+        UnkPspExecutable::from_path(file_name)
+    }
+}
+
+/// Returns the permissions of the input file to carry over to the output, or `None` if it is the
+/// standard input or on platforms without Unix permissions.
+fn input_permissions(file_name: &Path) -> io::Result<Option<fs::Permissions>> {
+    if cfg!(unix) && !is_stdio(file_name) {
+        Ok(Some(fs::metadata(file_name)?.permissions()))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Write the output file, or the standard output if the file name is `-`.
+///
+/// The file is written atomically, see [`write_atomic`], with the given permissions or the
+/// default ones of a new file.
+fn write_output(
+    file_name: &Path, content: &[u8], permissions: Option<fs::Permissions>,
+) -> io::Result<()> {
+    if is_stdio(file_name) {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(content)?;
+        stdout.flush()
+    } else {
+        write_atomic(file_name, content, permissions)
+    }
+}
+
+/// Write `content` to a temporary file next to `file_name`, then rename it to `file_name`.
+///
+/// An existing file is either left untouched or fully replaced, never partially overwritten. If
+/// the rename fails because the paths are on different filesystems, the temporary file is copied
+/// then removed instead.
+fn write_atomic(
+    file_name: &Path, content: &[u8], permissions: Option<fs::Permissions>,
+) -> io::Result<()> {
+    let mut temp_name = file_name.file_name().unwrap_or_default().to_owned();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_file = file_name.with_file_name(temp_name);
+
+    let result = fs::File::create(&temp_file).and_then(|mut file| {
+        file.write_all(content)?;
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        file.sync_all()
+    });
+    let result = result.and_then(|()| match fs::rename(&temp_file, file_name) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            fs::copy(&temp_file, file_name).map(|_| ())
+        },
+        result => result,
+    });
+
+    if temp_file.exists() {
+        // Left after a failed write, or copied across filesystems. A failure to remove it is not
+        // worth hiding the result of the write
+        let _ = fs::remove_file(&temp_file);
+    }
+
+    result
+}
+
+/// Print the PSP header fields that differ between two packed files, failing with
+/// [`Error::HeadersDiffer`] if there are any.
+fn compare(file_name: &Path, other: &Path, include_keys: bool) -> Result<(), Error> {
+    let packed_header = |file_name| {
+        let info = open_input(file_name)?.probe()?;
+        if !info.is_packed() {
+            return Err(Error::NotPacked);
+        }
+        Ok(header_fields(info.header(), include_keys))
+    };
+    let fields = packed_header(file_name)?;
+    let other_fields = packed_header(other)?;
+
+    let differing = fields
+        .iter()
+        .zip(&other_fields)
+        .filter(|((_, value), (_, other_value))| value != other_value)
+        .collect::<Vec<_>>();
+    if differing.is_empty() {
+        println!("The PSP headers are identical");
+        return Ok(());
+    }
+
+    println!("{:<20} {:<24} {}", "Field", file_name.display(), other.display());
+    for ((name, value), (_, other_value)) in differing {
+        println!("{name:<20} {value:<24} {other_value}");
+    }
+
+    Err(Error::HeadersDiffer)
+}
+
+/// The fields of `header` compared by `compare`, as `(name, value)`.
+fn header_fields(header: &PspHeader, include_keys: bool) -> Vec<(String, String)> {
+    let mut fields = vec![
+        ("tag", format!("0x{:08X}", header.tag)),
+        ("oe_tag", format!("0x{:08X}", header.oe_tag)),
+        (
+            "decrypt_mode",
+            header.decryption().map_or_else(
+                |_| format!("unknown (0x{:X})", header.decrypt_mode),
+                |mode| mode.to_string(),
+            ),
+        ),
+        ("devkit_version", format!("0x{:08X}", header.devkit_version)),
+        ("attribute", format!("0x{:04X}", header.attribute.bits())),
+        ("comp_attribute", format!("0x{:04X}", header.comp_attribute)),
+        (
+            "module_version",
+            format!("{}.{}", header.module_version_high, header.module_version_low),
+        ),
+        ("module_name", header.name().into_owned()),
+        ("version", header.version.to_string()),
+        ("elf_size", header.elf_size.to_string()),
+        ("psp_size", header.psp_size.to_string()),
+        ("comp_size", header.comp_size.to_string()),
+        ("overlap_size", header.overlap_size.to_string()),
+        ("entry", format!("0x{:08X}", header.entry)),
+        ("module_info_offset", format!("0x{:08X}", header.module_info_offset)),
+        ("bss_size", header.bss_size.to_string()),
+        ("num_segments", header.num_segments.to_string()),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_owned(), value))
+    .collect::<Vec<_>>();
+
+    for i in 0..header.seg_align.len() {
+        fields.push((format!("seg_align[{i}]"), format!("0x{:X}", header.seg_align[i])));
+        fields.push((format!("seg_addr[{i}]"), format!("0x{:08X}", header.seg_addr[i])));
+        fields.push((format!("seg_size[{i}]"), format!("0x{:08X}", header.seg_size[i])));
+    }
+
+    if include_keys {
+        let hex = |bytes: &[u8]| bytes.iter().map(|byte| format!("{byte:02X}")).collect::<String>();
+        fields.push(("key_data0".to_owned(), hex(&header.key_data0)));
+        fields.push(("key_data1".to_owned(), hex(&header.key_data1)));
+        fields.push(("key_data2".to_owned(), format!("0x{:08X}", header.key_data2)));
+        fields.push(("key_data3".to_owned(), hex(&header.key_data3)));
+    }
+
+    fields
+}
+
+fn extract(file_name: &Path, out_dir: &Path, verbose: bool) -> Result<(), Error> {
+    let file = open_input(file_name)?;
+    let sections = file.pbp_sections()?.ok_or(Error::NotPbp)?;
+
+    fs::create_dir_all(out_dir)?;
+    for section in sections.iter().filter(|section| !section.data().is_empty()) {
+        let path = out_dir.join(section.name());
+        fs::write(&path, section.data())?;
+
         if verbose {
-            eprintln!(
-                "psp-packer: WARNING: `output` option not used, overwriting `{}`",
-                file_name.display()
-            );
+            note!("Extracted `{}` ({} B)", path.display(), section.data().len());
+        }
+    }
+
+    Ok(())
+}
+
+fn make_pbp(
+    matches: &ArgMatches, prx_file: &Path, output_file: &Path, dry_run: bool, verbose: bool,
+) -> Result<(), Error> {
+    let read_part = |name: &str| match matches.get_one::<PathBuf>(name) {
+        Some(path) => fs::read(path),
+        None => Ok(Vec::new()),
+    };
+
+    let prx = open_input(prx_file)?;
+    let sfo = read_part("sfo")?;
+    let (icon0, icon1) = (read_part("icon0")?, read_part("icon1")?);
+    let (pic0, pic1) = (read_part("pic0")?, read_part("pic1")?);
+    let (snd0, psar) = (read_part("snd0")?, read_part("psar")?);
+
+    let pbp = PbpBuilder::new(&sfo, prx.as_bytes())
+        .icon0(&icon0)
+        .icon1(&icon1)
+        .pic0(&pic0)
+        .pic1(&pic1)
+        .snd0(&snd0)
+        .psar(&psar)
+        .build()?;
+
+    if dry_run {
+        if verbose {
+            warning!("not writing to file due to dry run");
         }
-        fs::write(file_name, compressed.as_bytes())?;
+    } else {
+        write_output(output_file, &pbp, None)?;
     }
 
     if verbose {
-        eprintln!("psp-packer: The file is a {}", compressed.kind());
-        eprintln!(
-            "psp-packer: Original file size: {:.2} KiB ({og_file_size} B)",
-            og_file_size as f64 / 1024.0
+        note!("Assembled `{}` ({} B)", output_file.display(), pbp.len());
+    }
+
+    Ok(())
+}
+
+/// Print the program headers as a table.
+fn print_segments(phdrs: &[Elf32Phdr]) {
+    println!(
+        "{:<12} {:<10} {:<10} {:<10} {:<10} {:<10} {:<5} Align",
+        "Type", "Offset", "VirtAddr", "PhysAddr", "FileSize", "MemSize", "Flags"
+    );
+    for phdr in phdrs {
+        let kind = phdr
+            .type_name()
+            .map_or_else(|| format!("0x{:08X}", phdr.p_type), str::to_owned);
+        let flags: String = [(4, 'R'), (2, 'W'), (1, 'X')]
+            .iter()
+            .map(|&(bit, flag)| if phdr.p_flags & bit != 0 { flag } else { '-' })
+            .collect();
+        println!(
+            "{kind:<12} 0x{:08X} 0x{:08X} 0x{:08X} 0x{:08X} 0x{:08X} {flags:<5} 0x{:X}",
+            phdr.p_offset, phdr.p_vaddr, phdr.p_paddr, phdr.p_filesz, phdr.p_memsz, phdr.p_align
         );
-        eprintln!(
-            "psp-packer: Compressed file size: {:.2} KiB ({} B)",
-            compressed.size() as f64 / 1024.0,
-            compressed.size()
+    }
+}
+
+fn print_sections(sections: &[ElfSection]) {
+    println!(
+        "{:<4} {:<22} {:<12} {:<10} {:<10} {:<10} Size",
+        "Nr", "Name", "Type", "Addr", "Offset", "Flags"
+    );
+    for (i, section) in sections.iter().enumerate() {
+        let shdr = section.header();
+        let name = section.name().map_or(Cow::Borrowed("?"), CStr::to_string_lossy);
+        let kind = shdr
+            .type_name()
+            .map_or_else(|| format!("0x{:08X}", shdr.sh_type), str::to_owned);
+        println!(
+            "{i:<4} {name:<22} {kind:<12} 0x{:08X} 0x{:08X} 0x{:08X} 0x{:X}",
+            shdr.sh_addr, shdr.sh_offset, shdr.sh_flags, shdr.sh_size
         );
     }
+}
 
-    Ok(())
+fn print_symbols(symbols: &ModuleSymbols) {
+    for (label, libraries) in [("Exports:", symbols.exports()), ("Imports:", symbols.imports())] {
+        println!("{label}");
+        if libraries.is_empty() {
+            println!("  none");
+        }
+        for library in libraries {
+            println!(
+                "  {:<28} version 0x{:04X} attribute 0x{:04X}",
+                library.name().unwrap_or("(system)"),
+                library.version(),
+                library.attribute()
+            );
+            for nid in library.functions() {
+                println!("    function 0x{nid:08X}");
+            }
+            for nid in library.variables() {
+                println!("    variable 0x{nid:08X}");
+            }
+        }
+    }
+}
+
+/// Colors of the `info` output, disabled when not printing to a terminal or with `NO_COLOR`.
+#[derive(Clone, Copy)]
+struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    const HEADER: Style = AnsiColor::Yellow.on_default().bold();
+    const HEX: Style = AnsiColor::Cyan.on_default();
+    const LABEL: Style = AnsiColor::Green.on_default();
+
+    fn new(matches: &ArgMatches) -> Self {
+        // Ok to unwrap as it has a default value
+        let enabled = match matches.get_one::<String>("color").unwrap().as_str() {
+            "always" => true,
+            "never" => false,
+            _ => {
+                io::stdout().is_terminal()
+                    && env::var_os("NO_COLOR").is_none_or(|no_color| no_color.is_empty())
+            },
+        };
+        Self { enabled }
+    }
+
+    /// Returns `text` in `style`, already padded as it should be aligned.
+    fn paint(self, style: Style, text: &str) -> String {
+        if self.enabled {
+            format!("{}{text}{}", style.render(), style.render_reset())
+        } else {
+            text.to_owned()
+        }
+    }
+
+    /// Returns `value` with its leading hex number colored.
+    fn value(self, value: &str) -> String {
+        match value.split_once(' ') {
+            Some((hex, rest)) if hex.starts_with("0x") => {
+                format!("{} {rest}", self.paint(Self::HEX, hex))
+            },
+            None if value.starts_with("0x") => self.paint(Self::HEX, value),
+            _ => value.to_owned(),
+        }
+    }
+
+    /// Print a table row, coloring its hex cells, each padded to `widths`.
+    fn row(self, cells: &[String], widths: &[usize]) {
+        let row: Vec<_> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, &width)| self.value(&format!("{cell:<width$}")))
+            .collect();
+        println!("  {}", row.join("  ").trim_end());
+    }
+
+    /// Print a table header row, each cell padded to `widths`.
+    fn header(self, cells: &[&str], widths: &[usize]) {
+        let row: Vec<_> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, &width)| format!("{cell:<width$}"))
+            .collect();
+        println!("  {}", self.paint(Self::HEADER, row.join("  ").trim_end()));
+    }
+}
+
+fn print_info(
+    info: &ExecutableInfo, sfo: Option<&SfoTable>, sections: Option<&[PbpSection]>,
+    palette: Palette,
+) {
+    let header = info.header();
+
+    let mut fields = vec![("Kind", info.kind().to_string())];
+    if let Some(sfo) = sfo {
+        for (label, key) in [("Title", "TITLE"), ("Disc ID", "DISC_ID"), ("Category", "CATEGORY")] {
+            if let Some(value) = sfo.get(key) {
+                fields.push((label, value.to_string()));
+            }
+        }
+    }
+    fields.extend([
+        ("Packed", if info.is_packed() { "yes" } else { "no" }.to_owned()),
+        ("Module name", header.name().into_owned()),
+        (
+            "Module version",
+            format!("{}.{}", header.module_version_high, header.module_version_low),
+        ),
+        (
+            "Attributes",
+            format!("0x{:04X} {:?}", header.attribute.bits(), header.attribute),
+        ),
+        ("Decrypt mode", match header.decryption() {
+            Ok(mode) => format!("0x{:X} {mode}", header.decrypt_mode),
+            Err(_) => format!("0x{:X} unknown", header.decrypt_mode),
+        }),
+        ("Devkit version", format!("0x{:08X}", header.devkit_version)),
+        ("Entry", format!("0x{:08X}", header.entry)),
+        ("BSS size", format!("{} B", header.bss_size)),
+    ]);
+    // Only set once packed
+    if header.psp_size != 0 {
+        fields.extend([
+            ("ELF size", format!("{} B", header.elf_size)),
+            ("PSP size", format!("{} B", header.psp_size)),
+            ("Compressed size", format!("{} B", header.comp_size)),
+            ("Tag", format!("0x{:08X}", header.tag)),
+            ("OE tag", format!("0x{:08X}", header.oe_tag)),
+        ]);
+    }
+    fields.push(("Segments", header.num_segments.to_string()));
+
+    let label_width = fields.iter().map(|(label, _)| label.len() + 1).max().unwrap_or(0);
+    for (label, value) in &fields {
+        let label = format!("{:<label_width$}", format!("{label}:"));
+        println!("{} {}", palette.paint(Palette::LABEL, &label), palette.value(value));
+    }
+
+    let widths = [2, 10, 10, 5];
+    palette.header(&["#", "Address", "Size", "Align"], &widths);
+    for i in 0..(header.num_segments as usize).min(header.seg_addr.len()) {
+        palette.row(
+            &[
+                i.to_string(),
+                format!("0x{:08X}", header.seg_addr[i]),
+                format!("0x{:08X}", header.seg_size[i]),
+                format!("0x{:X}", header.seg_align[i]),
+            ],
+            &widths,
+        );
+    }
+
+    if let Some(sections) = sections {
+        println!();
+        let widths = [10, 10, 12];
+        palette.header(&["Sub-file", "Offset", "Size"], &widths);
+        for section in sections {
+            palette.row(
+                &[
+                    section.name().to_owned(),
+                    format!("0x{:08X}", section.offset()),
+                    format!("{} B", section.data().len()),
+                ],
+                &widths,
+            );
+        }
+    }
 }
@@ -1,12 +1,15 @@
 use std::{
-    ffi::{c_void, CStr},
+    borrow::Cow,
+    ffi::CStr,
     fmt, fs,
-    io::{Cursor, Read, Write},
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
+    ops::Range,
     path::Path,
+    time::{Duration, Instant},
 };
 
 use bitflag_attr::bitflag;
-use flate2::{Compression, GzBuilder};
+use flate2::{read::GzDecoder, Compression, Crc, GzBuilder};
 use rand::Rng;
 
 #[cfg(feature = "dev")]
@@ -15,49 +18,356 @@ use bstr::ByteSlice;
 use crate::{
     elf::{Elf32Ehdr, Elf32Phdr, Elf32Shdr},
     error::Error,
+    sfo::SfoTable,
     utils::{self, AsBytes, TryFromBytes},
 };
 
 const PSP_HEADER_MAGIC: u32 = 0x5053507E;
 const PBP_HEADER_MAGIC: u32 = 0x50425000;
+/// `~SCE`, the header of signed retail modules.
+const SCE_HEADER_MAGIC: u32 = 0x4543537E;
+
+/// Size of the chunks fed to the gzip encoder between progress reports.
+const PROGRESS_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Known `(psp_tag, oe_tag)` pairs, the executable kind they are the default for, and a short
+/// description.
+pub const KNOWN_TAGS: &[(u32, u32, ExecutableKind, &str)] = &[
+    (0x457B06F0, 0x8555ABF2, ExecutableKind::UserPrx, "standalone user module"),
+    (0xDADADAF0, 0x55668D96, ExecutableKind::KernelPrx, "kernel module"),
+    (0xADF305F0, 0x7316308C, ExecutableKind::Pbp, "PBP (EBOOT) module"),
+];
+
+/// Check that the `(psp_tag, oe_tag)` pair is in [`KNOWN_TAGS`].
+pub fn validate_tags(psp_tag: u32, oe_tag: u32) -> Result<(), Error> {
+    if KNOWN_TAGS.iter().any(|&(tag, oe, ..)| tag == psp_tag && oe == oe_tag) {
+        Ok(())
+    } else {
+        Err(Error::UnknownTags {
+            tag: psp_tag,
+            oe_tag,
+        })
+    }
+}
+
+/// Check that a file of `len` bytes can hold an executable.
+///
+/// Fails with [`Error::EmptyFile`] if it is empty, and with [`Error::FileTooBig`] if it is bigger
+/// than `isize::MAX`, the size limit of allocations and slices on the platform.
+pub fn validate_size(len: usize) -> Result<(), Error> {
+    match len {
+        0 => Err(Error::EmptyFile),
+        len if len > isize::MAX as usize => Err(Error::FileTooBig),
+        _ => Ok(()),
+    }
+}
 
-const ISIZE_MAX: usize = 9_223_372_036_854_775_807;
+/// Detect the kind of executable in `exec` from its magic values and headers, without packing it.
+///
+/// Returns [`Error::AlreadyPacked`] for a packed PRX or a PBP containing one, and
+/// [`Error::Encrypted`] for a signed retail module.
+pub fn detect_kind(exec: &[u8]) -> Result<ExecutableKind, Error> {
+    let magic_at = |offset: usize| {
+        exec.get(offset..)
+            .and_then(|rest| rest.get(..4))
+            .map(|magic| u32::from_le_bytes(magic.try_into().unwrap()))
+    };
+
+    match magic_at(0).ok_or(Error::FileTooSmall)? {
+        PSP_HEADER_MAGIC => Err(Error::AlreadyPacked),
+        SCE_HEADER_MAGIC => Err(Error::Encrypted),
+        PBP_HEADER_MAGIC => {
+            let pbp = PbpHeader::ref_from_bytes(exec).map_err(|e| e.context("PbpHeader"))?;
+            match magic_at(pbp.prx_offset as usize) {
+                Some(PSP_HEADER_MAGIC) => Err(Error::AlreadyPacked),
+                Some(SCE_HEADER_MAGIC) => Err(Error::Encrypted),
+                _ => Ok(ExecutableKind::Pbp),
+            }
+        },
+        _ => {
+            let elf_header = Elf32Ehdr::from_bytes(exec).map_err(|e| e.context("Elf32Ehdr"))?;
+            elf_header.validate_prx()?;
+
+            let mod_info_phdr = find_module_info_phdr(exec, 0)?;
+            if mod_info_phdr.is_some_and(|phdr| (phdr.p_paddr & 0x80000000) != 0) {
+                Ok(ExecutableKind::KernelPrx)
+            } else {
+                Ok(ExecutableKind::UserPrx)
+            }
+        },
+    }
+}
 
 /// A PSP file of a unknown format.
 #[repr(transparent)]
 #[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
-pub struct UnkPspExecutable(Box<[u8]>);
+pub struct UnkPspExecutable(Content);
+
+/// The bytes of an [`UnkPspExecutable`], either read in memory or mapped from a file.
+enum Content {
+    Owned(Box<[u8]>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl Content {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Content::Owned(buf) => buf,
+            #[cfg(feature = "mmap")]
+            Content::Mapped(map) => map,
+        }
+    }
+
+    /// Returns the bytes mutably, copying a mapped file to memory first.
+    fn as_mut_bytes(&mut self) -> &mut [u8] {
+        #[cfg(feature = "mmap")]
+        if let Content::Mapped(map) = self {
+            *self = Content::Owned(Box::from(&map[..]));
+        }
+
+        match self {
+            Content::Owned(buf) => buf,
+            #[cfg(feature = "mmap")]
+            Content::Mapped(_) => unreachable!("mapped content was copied above"),
+        }
+    }
+}
+
+#[cfg(feature = "dev")]
+impl fmt::Debug for Content {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_bytes().as_bstr().fmt(f)
+    }
+}
+
+#[cfg(feature = "dev")]
+impl PartialEq for Content {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+#[cfg(feature = "dev")]
+impl Eq for Content {}
+
+#[cfg(feature = "dev")]
+impl PartialOrd for Content {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(feature = "dev")]
+impl Ord for Content {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+#[cfg(feature = "dev")]
+impl std::hash::Hash for Content {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state);
+    }
+}
 
 impl UnkPspExecutable {
     fn new(buf: Box<[u8]>) -> Self {
-        Self(buf)
+        Self(Content::Owned(buf))
     }
 
     pub fn from_path(path: &Path) -> Result<Self, Error> {
-        let file = fs::read(path)?;
-        if file.len() >= ISIZE_MAX {
-            return Err(Error::FileTooBig);
-        }
+        Self::from_bytes(fs::read(path)?)
+    }
 
-        Ok(Self::new(file.into_boxed_slice()))
+    /// Take ownership of an executable already in memory.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, Error> {
+        validate_size(bytes.len())?;
+
+        Ok(Self::new(bytes.into_boxed_slice()))
+    }
+
+    /// Copy an executable already in memory, see [`from_bytes`](Self::from_bytes) to avoid the
+    /// copy.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        Self::from_bytes(bytes.to_vec())
+    }
+
+    /// Map the executable file in memory instead of reading it.
+    ///
+    /// Only the parts that are patched while packing are copied, which avoids holding a second
+    /// copy of big PBP files in memory.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified, truncated or removed while the returned value is alive, by
+    /// this process or any other, as the mapping would change underneath it. Use
+    /// [`from_path`](Self::from_path) if that can't be guaranteed.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn from_path_mmap(path: &Path) -> Result<Self, Error> {
+        let file = fs::File::open(path)?;
+        // Safety: the caller guarantees that the file is not modified while mapped.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        validate_size(map.len())?;
+
+        Ok(Self(Content::Mapped(map)))
+    }
+
+    /// Read the whole executable from `reader` (e.g. the standard input).
+    pub fn from_reader(mut reader: impl Read) -> Result<Self, Error> {
+        let mut file = Vec::new();
+        reader.read_to_end(&mut file)?;
+        Self::from_bytes(file)
     }
 
     pub fn compress(self) -> Result<CompPspExecutable, Error> {
-        self.compress_impl(None, None)
+        self.compress_impl(&PackOptions::default())
     }
 
     pub fn compress_with_tags(self, psp_tag: u32, oe_tag: u32) -> Result<CompPspExecutable, Error> {
-        self.compress_impl(Some(psp_tag), Some(oe_tag))
+        self.compress_impl(&PackOptions::new().tags(psp_tag, oe_tag))
     }
 
-    /// Compress implementation
-    fn compress_impl(
-        mut self, psp_tag: Option<u32>, oe_tag: Option<u32>,
-    ) -> Result<CompPspExecutable, Error> {
+    /// Compress the executable using the given `options`.
+    pub fn compress_with(self, options: &PackOptions) -> Result<CompPspExecutable, Error> {
+        self.compress_impl(options)
+    }
+
+    /// Detect the kind of the executable without packing it, see [`detect_kind`].
+    pub fn detect_kind(&self) -> Result<ExecutableKind, Error> {
+        detect_kind(self.as_bytes())
+    }
+
+    /// Check that the ELF is for the MIPS CPU of the PSP, failing with [`Error::NotMips`]
+    /// otherwise.
+    ///
+    /// This is not checked when packing, so that callers can decide to only warn. Files that are
+    /// not an ELF or a PBP with an ELF are not checked, packing them reports why.
+    pub fn validate_machine(&self) -> Result<(), Error> {
+        let exec = self.as_bytes();
+        let elf_offset = PbpHeader::from_bytes(exec).map_or(0, |pbp| pbp.prx_offset as usize);
+
+        match exec.get(elf_offset..).map(Elf32Ehdr::from_bytes) {
+            Some(Ok(elf_header)) => elf_header.validate_machine(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that the module info attribute only has known [`ModInfoAttribute`] bits, failing
+    /// with [`Error::UnknownAttribute`] otherwise.
+    ///
+    /// Unknown bits may make the decrypt mode derived from the attribute wrong. Like
+    /// [`validate_machine`](Self::validate_machine), this is not checked when packing, and files
+    /// that can't be probed are not checked.
+    pub fn validate_attribute(&self) -> Result<(), Error> {
+        let Ok(info) = self.probe() else {
+            return Ok(());
+        };
+
+        match info.header.attribute.bits() & !ModInfoAttribute::all().bits() {
+            0 => Ok(()),
+            unknown => Err(Error::UnknownAttribute(unknown)),
+        }
+    }
+
+    /// Returns the program headers of the ELF, or of the PRX of a PBP.
+    ///
+    /// Fails with [`Error::AlreadyPacked`] for a packed file, whose ELF must be
+    /// [unpacked](Self::unpack) first.
+    ///
+    /// Unlike [`probe`](Self::probe), this doesn't need the module info nor valid segments, to
+    /// investigate why they are not found.
+    pub fn program_headers(&self) -> Result<Vec<Elf32Phdr>, Error> {
+        read_program_headers(self.as_bytes(), self.unpacked_elf_offset()?).map(Vec::from)
+    }
+
+    /// Returns the header of the ELF, inside the PBP for a PBP.
+    ///
+    /// Fails with [`Error::AlreadyPacked`] for a packed executable, which has to be unpacked
+    /// first.
+    pub fn elf_header(&self) -> Result<Elf32Ehdr, Error> {
+        let elf = &self.as_bytes()[self.unpacked_elf_offset()?..];
+        Elf32Ehdr::from_bytes(elf).map_err(|e| e.context("Elf32Ehdr"))
+    }
+
+    /// Returns the module info of the PRX, found as when packing.
+    ///
+    /// Fails with [`Error::AlreadyPacked`] for a packed executable, which has to be unpacked
+    /// first.
+    pub fn module_info(&self) -> Result<SceModuleInfo, Error> {
+        self.unpacked_elf_offset()?;
+        let info = self.probe_impl(&PackOptions::new().allow_no_bss(true))?;
+        let mod_info_slice =
+            self.as_bytes().get(info.mod_info_start..).ok_or(Error::FileTooSmall)?;
+        SceModuleInfo::from_bytes(mod_info_slice).map_err(|e| e.context("SceModuleInfo"))
+    }
+
+    /// Returns the section headers of the ELF, with their names resolved from the section name
+    /// string table.
+    ///
+    /// A missing or malformed string table is not an error, the affected names are `None`
+    /// instead. Fails with [`Error::AlreadyPacked`] for a packed executable, which has to be
+    /// unpacked first.
+    pub fn section_headers(&self) -> Result<Vec<ElfSection<'_>>, Error> {
+        read_named_sections(self.as_bytes(), self.unpacked_elf_offset()?)
+    }
+
+    /// Returns the libraries the module exports and imports, from the tables pointed to by its
+    /// module info.
+    ///
+    /// Fails with [`Error::BadLibraryTable`] if a table points outside the loaded segments, and
+    /// with [`Error::AlreadyPacked`] for a packed executable, which has to be unpacked first.
+    pub fn symbols(&self) -> Result<ModuleSymbols, Error> {
+        let exec = self.as_bytes();
+        let elf_offset = self.unpacked_elf_offset()?;
+        let info = self.probe_impl(&PackOptions::new().allow_no_bss(true))?;
+        let mod_info_slice = exec.get(info.mod_info_start..).ok_or(Error::FileTooSmall)?;
+        let mod_info =
+            SceModuleInfo::from_bytes(mod_info_slice).map_err(|e| e.context("SceModuleInfo"))?;
+        let phdrs = read_program_headers(exec, elf_offset)?;
+
+        Ok(ModuleSymbols {
+            exports: read_libraries(exec, elf_offset, &phdrs, mod_info.ent_top..mod_info.ent_end)?,
+            imports: read_libraries(
+                exec,
+                elf_offset,
+                &phdrs,
+                mod_info.stub_top..mod_info.stub_end,
+            )?,
+        })
+    }
+
+    /// Returns the offset of the ELF in the executable, failing with [`Error::AlreadyPacked`] if
+    /// it is packed.
+    fn unpacked_elf_offset(&self) -> Result<usize, Error> {
+        let exec = self.as_bytes();
+        let elf_offset = match PbpHeader::from_bytes(exec) {
+            Ok(pbp) => pbp.prx_range(exec.len())?.start,
+            Err(_) => 0,
+        };
+        let elf = exec.get(elf_offset..).ok_or(Error::FileTooSmall)?;
+        if PspHeader::from_header_bytes(elf).is_ok() {
+            return Err(Error::AlreadyPacked);
+        }
+
+        Ok(elf_offset)
+    }
+
+    /// Inspect the executable without packing it.
+    ///
+    /// For an already packed file, the existing [`PspHeader`] is read instead.
+    pub fn probe(&self) -> Result<ExecutableInfo, Error> {
+        self.probe_impl(&PackOptions::new())
+    }
+
+    /// Probe implementation, honoring the [`allow_no_bss`](PackOptions::allow_no_bss) and
+    /// [`kind`](PackOptions::kind) options.
+    fn probe_impl(&self, options: &PackOptions) -> Result<ExecutableInfo, Error> {
         let mut exec_size = self.size();
         let mut exec_kind = ExecutableKind::UserPrx;
         let mut exec_offset = 0;
-        let exec = self.as_mut_bytes();
+        let exec = self.as_bytes();
 
         let mut exec_cursor = Cursor::new(&exec);
         let mut file_magic = [0u8; 4];
@@ -65,20 +375,30 @@ impl UnkPspExecutable {
         let file_magic = u32::from_le_bytes(file_magic);
 
         if file_magic == PSP_HEADER_MAGIC {
-            return Err(Error::AlreadyPacked);
+            return ExecutableInfo::from_packed(exec, 0, false);
+        } else if file_magic == SCE_HEADER_MAGIC {
+            return Err(Error::Encrypted);
         }
 
         if file_magic == PBP_HEADER_MAGIC {
-            let pbp = PbpHeader::ref_from_bytes(exec)?;
+            let pbp = PbpHeader::ref_from_bytes(exec).map_err(|e| e.context("PbpHeader"))?;
+            let prx_range = pbp.prx_range(exec.len())?;
             exec_kind = ExecutableKind::Pbp;
-            exec_size = (pbp.psar_offset - pbp.prx_offset) as usize;
-            exec_offset = pbp.prx_offset as usize;
+            exec_size = prx_range.len();
+            exec_offset = prx_range.start;
+
+            let prx_magic = exec[exec_offset..].get(..4);
+            if prx_magic.is_some_and(|magic| magic == PSP_HEADER_MAGIC.to_le_bytes()) {
+                return ExecutableInfo::from_packed(exec, exec_offset, true);
+            } else if prx_magic.is_some_and(|magic| magic == SCE_HEADER_MAGIC.to_le_bytes()) {
+                return Err(Error::Encrypted);
+            }
         }
 
-        let elf_range = exec_offset..exec_size;
+        let elf_range = exec_offset..exec_offset + exec_size;
         let elf_header = {
-            let elf_slice = exec.get(elf_range.clone()).ok_or(Error::FileTooSmall)?;
-            Elf32Ehdr::from_bytes(elf_slice)?
+            let elf_slice = exec.get(elf_range).ok_or(Error::FileTooSmall)?;
+            Elf32Ehdr::from_bytes(elf_slice).map_err(|e| e.context("Elf32Ehdr"))?
         };
 
         // if exec_kind.is_pbp() && elf_header.is_prx() {
@@ -87,17 +407,21 @@ impl UnkPspExecutable {
         //     return Err(Error::NotPbp);
         // }
 
-        if exec_kind.is_prx() && !elf_header.is_prx() {
+        if exec_kind.is_prx() {
             // At this point, being a PRX is the only option, but if the header ELF header is not
             // marked with PRX magic value, then this is not a PSP PRX ELF file.
-            return Err(Error::NotPrx);
+            elf_header.validate_prx()?;
         }
 
         let mod_info_phdr = find_module_info_phdr(exec, exec_offset)?;
         let mod_info_shdr = find_segment(exec, exec_offset, c".rodata.sceModuleInfo")?;
 
-        let is_kernel_module =
-            mod_info_phdr.as_ref().is_some_and(|phdr| (phdr.p_paddr & 0x80000000) != 0);
+        let is_kernel_module = match options.kind {
+            Some(kind) if kind.is_pbp() && !exec_kind.is_pbp() => return Err(Error::NotPbp),
+            Some(kind) if !kind.is_pbp() && exec_kind.is_pbp() => return Err(Error::NotPrx),
+            Some(kind) => kind == ExecutableKind::KernelPrx,
+            None => mod_info_phdr.as_ref().is_some_and(|phdr| (phdr.p_paddr & 0x80000000) != 0),
+        };
 
         if is_kernel_module && exec_kind.is_pbp() {
             return Err(Error::KernelPbp);
@@ -111,23 +435,23 @@ impl UnkPspExecutable {
             // Should never happen as we already check for that case before
             (None, None) => return Err(Error::NoModuleInfo),
         };
-        let mod_info_start = exec_offset + (mod_info_off & 0x7FFFFFFF) as usize;
+        let mod_info_start = utils::add_offset(exec_offset, mod_info_off & 0x7FFFFFFF)?;
         let mod_info_slice = exec.get(mod_info_start..).ok_or(Error::FileTooSmall)?;
-        let mut mod_info = SceModuleInfo::from_bytes(mod_info_slice)?;
-
-
-        if (is_kernel_module && !mod_info.mod_attr.contains(ModInfoAttribute::KernelMode))
-            || (!is_kernel_module && mod_info.mod_attr.contains(ModInfoAttribute::KernelMode))
-        {
+        let mod_info =
+            SceModuleInfo::from_bytes(mod_info_slice).map_err(|e| e.context("SceModuleInfo"))?;
+
+        let mut mod_attr = mod_info.mod_attr;
+        if options.kind.is_some() {
+            // The forced kind decides the privileges
+            mod_attr.set(ModInfoAttribute::KernelMode, is_kernel_module);
+        } else if is_kernel_module != mod_attr.contains(ModInfoAttribute::KernelMode) {
             return Err(Error::MixedPrivileges);
         }
 
         let mut psp_header = PspHeader {
-            attribute: mod_info.mod_attr,
+            attribute: mod_attr,
             module_info_offset: mod_info_off,
-
-            // set comp attribute to use gzip
-            comp_attribute: 1,
+            comp_attribute: CompAttribute::Gzip.into(),
             module_version_low: mod_info.mod_version_low,
             module_version_high: mod_info.mod_version_high,
             ..Default::default()
@@ -137,122 +461,876 @@ impl UnkPspExecutable {
             *psp = info;
         }
 
-        psp_header.elf_size = exec_size as u32;
+        psp_header.elf_size = size_field("elf_size", exec_size as u64)?;
         psp_header.entry = elf_header.e_entry;
 
-        psp_header.num_segments = match elf_header.e_phnum {
-            0 => return Err(Error::NoSegments),
-            x if x > 4 => return Err(Error::NoSegments),
-            x => x as u8,
-        };
-
-        read_segments_bss_info(exec, exec_offset, &mut psp_header)?;
+        match read_segments_bss_info(exec, exec_offset, options.bss_from, &mut psp_header) {
+            Err(Error::BssNotFound) if options.allow_no_bss => psp_header.bss_size = 0,
+            res => res?,
+        }
 
         psp_header.set_decript_mode(exec_kind.is_pbp());
 
-        // Update mod_info for changes
-        mod_info.mod_attr = psp_header.attribute;
-        let mod_info_range = mod_info_start..mod_info_start + size_of::<SceModuleInfo>();
-        let mod_info_slice = exec.get_mut(mod_info_range).ok_or(Error::FileTooSmall)?;
-        mod_info_slice.copy_from_slice(mod_info.as_bytes());
+        Ok(ExecutableInfo {
+            kind: exec_kind,
+            packed: false,
+            header: psp_header,
+            elf_offset: exec_offset,
+            elf_size: exec_size,
+            mod_info_start,
+        })
+    }
 
-        psp_header.tag = psp_tag.unwrap_or_else(|| default_psp_tag_handler(exec_kind));
-        psp_header.oe_tag = oe_tag.unwrap_or_else(|| default_oe_tag_handler(exec_kind));
+    /// Parse the PARAM.SFO of a PBP.
+    ///
+    /// Returns `None` if the executable is not a PBP.
+    pub fn sfo(&self) -> Result<Option<SfoTable>, Error> {
+        let exec = self.as_bytes();
+        if !exec.starts_with(&PBP_HEADER_MAGIC.to_le_bytes()) {
+            return Ok(None);
+        }
 
-        // Fill key data with random data
-        let mut rnd = utils::rand();
-        rnd.fill(&mut psp_header.key_data0);
-        rnd.fill(&mut psp_header.key_data1);
-        rnd.fill(&mut psp_header.key_data3);
+        let pbp = PbpHeader::from_bytes(exec).map_err(|e| e.context("PbpHeader"))?;
+        pbp.validate(exec.len())?;
+        let sfo_slice = &exec[pbp.sfo_offset as usize..pbp.icon0_offset as usize];
 
-        let guess_size = utils::gzip_max_compressed_size(exec_size);
-        let mut compressed_cursor =
-            Cursor::new(Vec::with_capacity(guess_size + size_of::<PspHeader>()));
+        SfoTable::from_bytes(sfo_slice).map(Some)
+    }
 
-        // Skip the psp_header from the compressed buffer
-        compressed_cursor.set_position(size_of_val(&psp_header) as u64);
-        let elf_slice = exec.get(elf_range).ok_or(Error::FileTooSmall)?;
-        let mut gzip = GzBuilder::new()
-            .operating_system(0x0B)
-            .write(&mut compressed_cursor, Compression::best());
-        gzip.write_all(elf_slice)?;
-        gzip.finish()?;
+    /// Returns the header of a PBP, with the offsets of its sub-files.
+    ///
+    /// Returns `None` if the executable is not a PBP.
+    pub fn pbp_header(&self) -> Result<Option<PbpHeader>, Error> {
+        let exec = self.as_bytes();
+        if !exec.starts_with(&PBP_HEADER_MAGIC.to_le_bytes()) {
+            return Ok(None);
+        }
 
-        // Update psp header
-        let new_size = compressed_cursor.get_ref().len();
-        psp_header.comp_size = (new_size - size_of::<PspHeader>()) as u32;
-        psp_header.psp_size = new_size as u32;
-        let exec_comp_size = new_size - size_of::<PspHeader>();
+        let pbp = PbpHeader::from_bytes(exec).map_err(|e| e.context("PbpHeader"))?;
+        pbp.validate(exec.len())?;
+        Ok(Some(pbp))
+    }
 
-        // dbg!("FINAL_PSP_HEADER", &psp_header);
+    /// Returns the sub-files of a PBP, in file order.
+    ///
+    /// Sizes are derived from the difference between consecutive offsets. Returns `None` if the
+    /// executable is not a PBP.
+    pub fn pbp_sections(&self) -> Result<Option<Vec<PbpSection<'_>>>, Error> {
+        let exec = self.as_bytes();
+        if !exec.starts_with(&PBP_HEADER_MAGIC.to_le_bytes()) {
+            return Ok(None);
+        }
 
-        // write psp header and set position back
-        let last_pos = compressed_cursor.position();
-        compressed_cursor.set_position(0);
-        compressed_cursor.write_all(psp_header.as_bytes())?;
-        compressed_cursor.set_position(last_pos);
+        let pbp = PbpHeader::from_bytes(exec).map_err(|e| e.context("PbpHeader"))?;
+        pbp.validate(exec.len())?;
+        let offsets = pbp.sections();
+
+        let sections = offsets
+            .iter()
+            .enumerate()
+            .map(|(i, &(name, offset))| {
+                let start = offset as usize;
+                let end = offsets.get(i + 1).map_or(exec.len(), |&(_, next)| next as usize);
+
+                PbpSection {
+                    name,
+                    offset: start,
+                    data: &exec[start..end],
+                }
+            })
+            .collect();
+
+        Ok(Some(sections))
+    }
 
+    /// Offset of the PSP header of an already packed executable.
+    fn packed_offset(&self) -> Result<usize, Error> {
+        let exec = self.as_bytes();
+        let psp_magic = PSP_HEADER_MAGIC.to_le_bytes();
+        let sce_magic = SCE_HEADER_MAGIC.to_le_bytes();
+
+        if exec.starts_with(&PBP_HEADER_MAGIC.to_le_bytes()) {
+            let pbp = PbpHeader::from_bytes(exec).map_err(|e| e.context("PbpHeader"))?;
+            let prx_offset = pbp.prx_offset as usize;
+            let prx = exec.get(prx_offset..).unwrap_or_default();
+            if prx.starts_with(&psp_magic) {
+                return Ok(prx_offset);
+            } else if prx.starts_with(&sce_magic) {
+                return Err(Error::Encrypted);
+            }
+        } else if exec.starts_with(&psp_magic) {
+            return Ok(0);
+        } else if exec.starts_with(&sce_magic) {
+            return Err(Error::Encrypted);
+        }
 
-        // if PBP we need to insert the PBP header/icons etc
-        if exec_kind.is_pbp() {
-            let pbp_header = exec.get(..exec_offset).ok_or(Error::FileTooSmall)?;
-            let pbp_icon_start = exec_offset + exec_size;
-            let pbp_icons = exec.get(pbp_icon_start..).ok_or(Error::FileTooSmall)?;
+        Err(Error::NotPacked)
+    }
 
-            compressed_cursor.write_all(pbp_icons)?;
-            compressed_cursor.set_position(0);
-            compressed_cursor.write_all(pbp_header)?;
+    /// Structurally validate an already packed executable.
+    pub fn verify(&self) -> Result<(), Error> {
+        VerifyCheck::ALL.iter().try_for_each(|&check| self.verify_check(check))
+    }
 
+    /// Run a single check of [`verify`](Self::verify).
+    pub fn verify_check(&self, check: VerifyCheck) -> Result<(), Error> {
+        let offset = self.packed_offset()?;
+        let exec = self.as_bytes().get(offset..).ok_or(Error::FileTooSmall)?;
+        let header = PspHeader::from_bytes(exec).map_err(|e| e.context("PspHeader"))?;
+
+        match check {
+            VerifyCheck::Signature => {
+                if header.signature != PSP_HEADER_MAGIC {
+                    return Err(Error::NotPacked);
+                }
+            },
+            VerifyCheck::Sizes => {
+                // The PSP size is larger if the packed PRX is padded
+                let expected = size_of::<PspHeader>() as u64 + header.comp_size as u64;
+                if expected > header.psp_size as u64 {
+                    return Err(Error::Corrupted("header and compressed sizes mismatch"));
+                }
+            },
+            VerifyCheck::Segments => {
+                if !(1..=4).contains(&header.num_segments) {
+                    return Err(Error::Corrupted("invalid number of segments"));
+                }
+            },
+            VerifyCheck::Payload => {
+                decompress_payload(&header, exec)?;
+            },
+        }
+
+        Ok(())
+    }
 
-            let compressed_pbp_slice = compressed_cursor.get_mut().as_mut_slice();
-            let pbp = PbpHeader::mut_from_bytes(compressed_pbp_slice)?;
-            pbp.psar_offset = (exec_offset + exec_comp_size) as u32;
+    /// Unpack an already packed executable.
+    ///
+    /// Returns the original ELF of a packed PRX, or the PBP with its original PRX for a packed PBP.
+    pub fn unpack(&self) -> Result<UnkPspExecutable, Error> {
+        let offset = self.packed_offset()?;
+        let exec = self.as_bytes();
+        let psp_slice = exec.get(offset..).ok_or(Error::FileTooSmall)?;
+        let header = PspHeader::from_packed(psp_slice)?;
+        let elf = decompress_payload(&header, psp_slice)?;
+
+        if offset == 0 {
+            return Ok(Self::new(elf.into_boxed_slice()));
         }
 
+        // Put the ELF back in place of the packed PRX, moving the PSAR after it
+        let mut pbp = PbpHeader::from_bytes(exec).map_err(|e| e.context("PbpHeader"))?;
+        let psar = &exec[pbp.prx_range(exec.len())?.end..];
+        pbp.psar_offset = size_field("psar_offset", (offset + elf.len()) as u64)?;
+
+        let mut unpacked = Vec::with_capacity(offset + elf.len() + psar.len());
+        unpacked.extend_from_slice(&exec[..offset]);
+        unpacked.extend_from_slice(&elf);
+        unpacked.extend_from_slice(psar);
+        unpacked[..size_of::<PbpHeader>()].copy_from_slice(pbp.as_bytes());
+
+        Ok(Self::new(unpacked.into_boxed_slice()))
+    }
+
+    /// Compress the executable, writing the packed file into `writer` at its current position.
+    ///
+    /// The headers are written as placeholders and patched in place once the sizes are known,
+    /// so the packed file is never fully held in memory. Unlike [`compress`](Self::compress),
+    /// the ELF is kept gzip compressed even if that doesn't make it smaller, as `writer` can't
+    /// be truncated.
+    ///
+    /// Returns the number of bytes written.
+    pub fn compress_to<W: Write + Seek>(
+        self, writer: &mut W, options: &PackOptions,
+    ) -> Result<u64, Error> {
+        self.compress_into(writer, options).map(|packed| packed.size)
+    }
+
+    /// Compress implementation
+    fn compress_impl(self, options: &PackOptions) -> Result<CompPspExecutable, Error> {
+        let guess_size = utils::gzip_max_compressed_size(self.size());
+        let mut compressed_cursor =
+            Cursor::new(Vec::with_capacity(guess_size + size_of::<PspHeader>()));
+        let mut packed = self.compress_into(&mut compressed_cursor, options)?;
+
+        // If gzip didn't shrink the ELF, store it uncompressed instead, reusing the buffer so a
+        // large PBP is never held twice
+        if options.level > 0 && packed.header.comp_size >= packed.header.elf_size {
+            let options = options.clone().level(0);
+            compressed_cursor.get_mut().clear();
+            compressed_cursor.set_position(0);
+            packed = self.compress_into(&mut compressed_cursor, &options)?;
+        }
 
         Ok(CompPspExecutable::new(
             compressed_cursor.into_inner().into_boxed_slice(),
-            exec_kind,
+            packed,
         ))
     }
 
+    /// Write the packed executable into `writer`, patching the headers in place.
+    fn compress_into<W: Write + Seek>(
+        &self, writer: &mut W, options: &PackOptions,
+    ) -> Result<Packed, Error> {
+        let mut phase_start = options.timings.then(Instant::now);
+
+        if let Some(align) = options.pad_to.filter(|align| !align.is_power_of_two()) {
+            return Err(Error::PadAlign(align));
+        }
+
+        let info = self.probe_impl(options)?;
+        if info.is_packed() {
+            if options.force {
+                let mut options = options.clone();
+                if options.preserve_reserved {
+                    let original = info.header();
+                    options.reserved = Some((original.reserved, original.reserved2));
+                    options.scheck = Some(original.scheck);
+                }
+                let mut packed = self.unpack()?.compress_into(writer, &options)?;
+                packed.original_size = self.size();
+                return Ok(packed);
+            }
+            return Err(Error::AlreadyPacked);
+        }
+
+        let ExecutableInfo {
+            kind: exec_kind,
+            header: mut psp_header,
+            elf_offset: exec_offset,
+            elf_size: exec_size,
+            mod_info_start,
+            ..
+        } = info;
+        let elf_range = exec_offset..exec_offset + exec_size;
+        let exec = self.as_bytes();
+
+        #[cfg(feature = "logging")]
+        log::debug!(
+            kind:% = exec_kind,
+            elf_offset = exec_offset,
+            elf_size = exec_size;
+            "Probed the executable"
+        );
+
+        // Update mod_info for changes
+        let mod_info_slice = exec.get(mod_info_start..).ok_or(Error::FileTooSmall)?;
+        let mut mod_info =
+            SceModuleInfo::from_bytes(mod_info_slice).map_err(|e| e.context("SceModuleInfo"))?;
+        mod_info.mod_attr = psp_header.attribute;
+        if let Some((major, minor)) = options.module_version {
+            mod_info.mod_version_high = major;
+            mod_info.mod_version_low = minor;
+            psp_header.module_version_high = major;
+            psp_header.module_version_low = minor;
+        }
+        if let Some(name) = &options.name {
+            utils::copy_name(&mut mod_info.mod_name, name);
+            mod_info.terminal = 0;
+            psp_header.module_name.fill(0);
+            psp_header.module_name[..mod_info.mod_name.len()].copy_from_slice(&mod_info.mod_name);
+        }
+
+        // Stripping keeps the module info at the same offset in the ELF
+        let elf = exec.get(elf_range.clone()).ok_or(Error::FileTooSmall)?;
+        let mod_info_start = mod_info_start - elf_range.start;
+        let mod_info_end = mod_info_start + size_of::<SceModuleInfo>();
+        let elf = if options.strip {
+            let stripped = strip_elf(elf, mod_info_start..mod_info_end)?;
+            psp_header.elf_size = size_field("elf_size", stripped.len() as u64)?;
+            Cow::Owned(stripped)
+        } else {
+            Cow::Borrowed(elf)
+        };
+        let stripped_size = options.strip.then(|| exec_size - elf.len());
+
+        // The ELF with the updated mod_info, without copying the rest of it
+        let elf_parts = [
+            elf.get(..mod_info_start).ok_or(Error::FileTooSmall)?,
+            mod_info.as_bytes(),
+            elf.get(mod_info_end..).ok_or(Error::FileTooSmall)?,
+        ];
+
+        let (default_psp_tag, default_oe_tag) = exec_kind.default_tags();
+        psp_header.tag = options.psp_tag.unwrap_or(default_psp_tag);
+        psp_header.oe_tag = options.oe_tag.unwrap_or(default_oe_tag);
+
+        if let Some(decrypt_mode) = options.decrypt_mode {
+            psp_header.decrypt_mode = decrypt_mode.into();
+        }
+        // Always one of the modes, set from the module attributes unless given
+        let decrypt_mode = psp_header.decryption()?;
+        if let Some(devkit_version) = options.devkit_version {
+            psp_header.devkit_version = devkit_version;
+        }
+
+        // Fill key data with the given or random data
+        if let Some((key_data0, key_data1, key_data3)) = options.key_data {
+            psp_header.key_data0 = key_data0;
+            psp_header.key_data1 = key_data1;
+            psp_header.key_data3 = key_data3;
+        } else if !options.no_random {
+            let mut rnd = options.seed.map_or_else(utils::rand, utils::rand_from_seed);
+            rnd.fill(&mut psp_header.key_data0);
+            rnd.fill(&mut psp_header.key_data1);
+            rnd.fill(&mut psp_header.key_data3);
+        }
+        if let Some(scheck) = options.scheck {
+            psp_header.scheck = scheck;
+        }
+        if let Some((reserved, reserved2)) = options.reserved {
+            psp_header.reserved = reserved;
+            psp_header.reserved2 = reserved2;
+        }
+
+        let elf_len = elf_parts.iter().map(|part| part.len()).sum();
+        let mut elf_crc = Crc::new();
+        for part in elf_parts {
+            elf_crc.update(part);
+        }
+
+        let parse_time = lap(&mut phase_start);
+
+        // if PBP, the PBP header and the sub-files before the PRX come first
+        let start = writer.stream_position()?;
+        if exec_kind.is_pbp() {
+            writer.write_all(exec.get(..exec_offset).ok_or(Error::FileTooSmall)?)?;
+        }
+
+        // Write a placeholder psp header, patched once the compressed size is known
+        let psp_start = writer.stream_position()?;
+        writer.write_all(psp_header.as_bytes())?;
+
+        let comp_attribute = if options.level > 0 {
+            CompAttribute::Gzip
+        } else {
+            CompAttribute::Stored
+        };
+        psp_header.comp_attribute = options.comp_attribute.unwrap_or(comp_attribute.into());
+
+        #[cfg(feature = "logging")]
+        log::trace!(
+            level = options.level,
+            comp_attribute:% = comp_attribute;
+            "Compressing the ELF"
+        );
+
+        if comp_attribute == CompAttribute::Gzip {
+            let gzip = GzBuilder::new().operating_system(0x0B).write(
+                utils::CopyWriter::new(&mut *writer, options.overlap || options.self_check),
+                Compression::new(options.level),
+            );
+            let mut gzip =
+                utils::ProgressWriter::new(gzip, elf_len, options.progress.unwrap_or(|_, _| {}));
+            // Feed the encoder in chunks so the progress is reported while compressing
+            for chunk in elf_parts.iter().flat_map(|part| part.chunks(PROGRESS_CHUNK_SIZE)) {
+                gzip.write_all(chunk)?;
+            }
+            let payload = gzip.into_inner().finish()?.into_copy();
+            if let Some(payload) = payload {
+                if options.self_check {
+                    self_check(&payload, &elf_parts)?;
+                }
+                if options.overlap {
+                    psp_header.overlap_size = overlap_size(&payload, elf_len)?;
+                }
+            }
+        } else {
+            for part in elf_parts {
+                writer.write_all(part)?;
+            }
+            if options.overlap {
+                // Copying never writes ahead of the read position
+                psp_header.overlap_size = u16::try_from(elf_len).unwrap_or(u16::MAX);
+            }
+        }
+
+        let compress_time = lap(&mut phase_start);
+
+        // Update psp header, the compressed size excludes the padding
+        let unpadded_size = writer.stream_position()? - psp_start;
+        if let Some(align) = options.pad_to {
+            let padding = unpadded_size.next_multiple_of(align.into()) - unpadded_size;
+            io::copy(&mut io::repeat(0).take(padding), writer)?;
+        }
+        let psp_size = writer.stream_position()? - psp_start;
+        psp_header.psp_size = size_field("psp_size", psp_size)?;
+        psp_header.comp_size =
+            size_field("comp_size", unpadded_size - size_of::<PspHeader>() as u64)?;
+
+        // if PBP, the sub-files after the PRX come last
+        if exec_kind.is_pbp() {
+            writer.write_all(exec.get(elf_range.end..).ok_or(Error::FileTooSmall)?)?;
+        }
+        let end = writer.stream_position()?;
+
+        // write psp header and set position back
+        writer.seek(SeekFrom::Start(psp_start))?;
+        writer.write_all(psp_header.as_bytes())?;
+
+        if exec_kind.is_pbp() {
+            let mut pbp = PbpHeader::from_bytes(exec).map_err(|e| e.context("PbpHeader"))?;
+            pbp.psar_offset = size_field("psar_offset", exec_offset as u64 + psp_size)?;
+            writer.seek(SeekFrom::Start(start))?;
+            writer.write_all(pbp.as_bytes())?;
+        }
+        writer.seek(SeekFrom::Start(end))?;
+        let write_time = lap(&mut phase_start);
+
+        #[cfg(feature = "logging")]
+        log::debug!(
+            elf_size = psp_header.elf_size,
+            comp_size = psp_header.comp_size,
+            psp_tag:% = format_args!("0x{:08X}", psp_header.tag),
+            oe_tag:% = format_args!("0x{:08X}", psp_header.oe_tag),
+            decrypt_mode:% = decrypt_mode;
+            "Packed the executable"
+        );
+
+        Ok(Packed {
+            kind: exec_kind,
+            header: psp_header,
+            decrypt_mode,
+            elf_crc32: elf_crc.sum(),
+            original_size: self.size(),
+            stripped_size,
+            size: end - start,
+            header_offset: (psp_start - start) as usize,
+            timings: options.timings.then_some(PackTimings {
+                parse: parse_time,
+                compress: compress_time,
+                write: write_time,
+            }),
+        })
+    }
+
     /// File size in bytes.
     pub fn size(&self) -> usize {
-        self.0.len()
+        self.0.as_bytes().len()
     }
 
-    #[allow(unused, reason = "maybe use in the future (maybe as lib)")]
-    fn as_bytes(&self) -> &[u8] {
+    pub fn as_bytes(&self) -> &[u8] {
         self.as_ref()
     }
-
-    fn as_mut_bytes(&mut self) -> &mut [u8] {
-        self.as_mut()
-    }
 }
 
 impl AsRef<[u8]> for UnkPspExecutable {
     fn as_ref(&self) -> &[u8] {
-        self.0.as_ref()
+        self.0.as_bytes()
     }
 }
 
 impl AsMut<[u8]> for UnkPspExecutable {
     fn as_mut(&mut self) -> &mut [u8] {
-        self.0.as_mut()
+        self.0.as_mut_bytes()
     }
 }
 
+/// What [`UnkPspExecutable::compress_into`] wrote.
+#[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
+struct Packed {
+    kind: ExecutableKind,
+    /// The PSP header, as written.
+    header: PspHeader,
+    decrypt_mode: DecryptMode,
+    elf_crc32: u32,
+    original_size: usize,
+    stripped_size: Option<usize>,
+    size: u64,
+    /// Offset of the PSP header in the packed file.
+    header_offset: usize,
+    timings: Option<PackTimings>,
+}
+
+/// Information about a PSP executable, gathered without packing it.
+#[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
+pub struct ExecutableInfo {
+    kind: ExecutableKind,
+    packed: bool,
+    header: PspHeader,
+    elf_offset: usize,
+    elf_size: usize,
+    mod_info_start: usize,
+}
+
+impl ExecutableInfo {
+    fn from_packed(exec: &[u8], offset: usize, is_pbp: bool) -> Result<Self, Error> {
+        let psp_slice = exec.get(offset..).ok_or(Error::FileTooSmall)?;
+        let header = PspHeader::from_packed(psp_slice)?;
+
+        let kind = if is_pbp {
+            ExecutableKind::Pbp
+        } else if header.attribute.contains(ModInfoAttribute::KernelMode) {
+            ExecutableKind::KernelPrx
+        } else {
+            ExecutableKind::UserPrx
+        };
+
+        Ok(Self {
+            kind,
+            packed: true,
+            elf_offset: offset,
+            elf_size: header.psp_size as usize,
+            mod_info_start: 0,
+            header,
+        })
+    }
+
+    /// Returns the PSP executable kind.
+    pub fn kind(&self) -> ExecutableKind {
+        self.kind
+    }
+
+    /// Returns `true` if the executable is already packed.
+    pub fn is_packed(&self) -> bool {
+        self.packed
+    }
+
+    /// Returns the PSP header.
+    ///
+    /// For a packed executable, this is the header found in the file. Otherwise, it is the header
+    /// that packing would produce, without tags, key data and compressed sizes.
+    pub fn header(&self) -> &PspHeader {
+        &self.header
+    }
+}
+
+/// A structural check done when verifying a packed executable.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "dev", derive(Debug, PartialOrd, Ord, Hash))]
+pub enum VerifyCheck {
+    /// The PSP header signature magic is valid.
+    Signature,
+    /// The header size plus the compressed size is the PSP size, or less if it is padded.
+    Sizes,
+    /// The number of segments is in `1..=4`.
+    Segments,
+    /// The payload decompresses to exactly the ELF size.
+    Payload,
+}
+
+impl VerifyCheck {
+    /// All checks, in the order they are run.
+    pub const ALL: [Self; 4] = [Self::Signature, Self::Sizes, Self::Segments, Self::Payload];
+}
+
+impl fmt::Display for VerifyCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyCheck::Signature => f.pad("signature magic"),
+            VerifyCheck::Sizes => f.pad("header size + compressed size <= PSP size"),
+            VerifyCheck::Segments => f.pad("number of segments in 1..=4"),
+            VerifyCheck::Payload => f.pad("payload decompresses to the ELF size"),
+        }
+    }
+}
+
+/// Options that control how a PSP executable is packed.
+#[derive(Clone)]
+#[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
+pub struct PackOptions {
+    psp_tag: Option<u32>,
+    oe_tag: Option<u32>,
+    level: u32,
+    devkit_version: Option<u32>,
+    module_version: Option<(u8, u8)>,
+    name: Option<String>,
+    progress: Option<fn(usize, usize)>,
+    force: bool,
+    preserve_reserved: bool,
+    allow_no_bss: bool,
+    bss_from: BssSource,
+    decrypt_mode: Option<DecryptMode>,
+    seed: Option<u64>,
+    no_random: bool,
+    key_data: Option<([u8; 0x30], [u8; 0x10], [u8; 0x1C])>,
+    scheck: Option<[u8; 0x58]>,
+    /// The `reserved` and `reserved2` fields, only set when preserving them while repacking.
+    reserved: Option<([u32; 5], [u32; 2])>,
+    comp_attribute: Option<u16>,
+    kind: Option<ExecutableKind>,
+    overlap: bool,
+    self_check: bool,
+    timings: bool,
+    pad_to: Option<u32>,
+    strip: bool,
+}
+
+impl PackOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the given tags instead of the defaults for the executable kind.
+    pub fn tags(mut self, psp_tag: u32, oe_tag: u32) -> Self {
+        self.psp_tag = Some(psp_tag);
+        self.oe_tag = Some(oe_tag);
+        self
+    }
+
+    /// Set the gzip compression level, from 0 to 9.
+    ///
+    /// Level 0 stores the ELF uncompressed. Values above 9 are clamped to 9.
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = level.min(9);
+        self
+    }
+
+    /// Use the given devkit version instead of the one implied by the decrypt mode.
+    pub fn devkit_version(mut self, devkit_version: u32) -> Self {
+        self.devkit_version = Some(devkit_version);
+        self
+    }
+
+    /// Use the given module version instead of the one in the module info.
+    pub fn module_version(mut self, major: u8, minor: u8) -> Self {
+        self.module_version = Some((major, minor));
+        self
+    }
+
+    /// Use the given module name instead of the one in the module info.
+    ///
+    /// The name is truncated to 26 bytes, on a char boundary, to fit the module info.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Call `progress` with the number of ELF bytes compressed so far and the ELF size.
+    pub fn progress(mut self, progress: fn(usize, usize)) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Unpack and repack already packed executables instead of failing with
+    /// [`Error::AlreadyPacked`].
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// When [repacking](Self::force), copy the `reserved`, `reserved2` and `scheck` fields of the
+    /// original header instead of zeroing them.
+    ///
+    /// Some encrypters stash data there, which is lost otherwise.
+    pub fn preserve_reserved(mut self, preserve_reserved: bool) -> Self {
+        self.preserve_reserved = preserve_reserved;
+        self
+    }
+
+    /// With [`BssSource::Section`], pack ELFs without a `.bss` section with a BSS size of 0,
+    /// instead of failing with [`Error::BssNotFound`].
+    ///
+    /// On hardware, the module then has no zero-initialized region after its segments.
+    pub fn allow_no_bss(mut self, allow_no_bss: bool) -> Self {
+        self.allow_no_bss = allow_no_bss;
+        self
+    }
+
+    /// Set where the BSS size is read from, [`BssSource::Segments`] by default.
+    pub fn bss_from(mut self, bss_from: BssSource) -> Self {
+        self.bss_from = bss_from;
+        self
+    }
+
+    /// Use the given decrypt mode instead of the one implied by the module attributes.
+    pub fn decrypt_mode(mut self, decrypt_mode: DecryptMode) -> Self {
+        self.decrypt_mode = Some(decrypt_mode);
+        self
+    }
+
+    /// Generate the header key data from `seed`, so that packing the same file twice produces
+    /// the same output.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Leave the header key data zeroed instead of filling it with random data.
+    ///
+    /// Takes precedence over [`seed`](Self::seed).
+    pub fn no_random(mut self, no_random: bool) -> Self {
+        self.no_random = no_random;
+        self
+    }
+
+    /// Reuse the key data of `header`, e.g. of another packed file, instead of generating it.
+    ///
+    /// Takes precedence over [`seed`](Self::seed) and [`no_random`](Self::no_random).
+    pub fn key_data(mut self, header: &PspHeader) -> Self {
+        self.key_data = Some((header.key_data0, header.key_data1, header.key_data3));
+        self
+    }
+
+    /// Write the given [`scheck`](PspHeader::scheck) block instead of leaving it zeroed.
+    ///
+    /// The block is not computed: it is the signature check of a signed module, only meaningful
+    /// when reusing the one of a header signed for the same payload.
+    pub fn scheck(mut self, scheck: [u8; 0x58]) -> Self {
+        self.scheck = Some(scheck);
+        self
+    }
+
+    /// Write the given [`comp_attribute`](PspHeader::comp_attribute) instead of the one of the
+    /// compression method, for research on the loader.
+    ///
+    /// The payload is still compressed according to the [`level`](Self::level), any value is
+    /// written as is, even if it's not a known [`CompAttribute`].
+    pub fn comp_attribute(mut self, comp_attribute: u16) -> Self {
+        self.comp_attribute = Some(comp_attribute);
+        self
+    }
+
+    /// Pack as the given kind instead of the detected one.
+    ///
+    /// Forcing a PRX kind sets or clears the kernel mode attribute accordingly. Forcing
+    /// [`ExecutableKind::Pbp`] on a PRX fails with [`Error::NotPbp`], and a PRX kind on a PBP with
+    /// [`Error::NotPrx`].
+    pub fn kind(mut self, kind: ExecutableKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Set [`PspHeader::overlap_size`] so the loader can decompress the module in place.
+    ///
+    /// The overlap size is the number of bytes by which the compressed payload, placed at the end
+    /// of the load buffer, may overlap the decompressed ELF written from its start. The bigger it
+    /// is, the less memory the loader needs on top of the ELF size while loading. It is computed
+    /// by decompressing the payload, so that the ELF being written never reaches payload bytes
+    /// that are not read yet, with a safety margin, and capped to `u16::MAX`.
+    ///
+    /// This is experimental, it is left at 0 (no overlap) by default.
+    pub fn overlap(mut self, overlap: bool) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Zero-pad the packed PRX to a multiple of `align` bytes, which must be a power of two.
+    ///
+    /// The PSP size of the header includes the padding, not the compressed size. In a PBP, the
+    /// padding ends DATA.PSP and DATA.PSAR starts after it. Compressing fails with
+    /// [`Error::PadAlign`] if `align` is not a power of two.
+    pub fn pad_to(mut self, align: u32) -> Self {
+        self.pad_to = Some(align);
+        self
+    }
+
+    /// Strip what the loader doesn't need from the ELF before compressing it.
+    ///
+    /// The section header table and everything after the last segment, like the symbol table,
+    /// are dropped, and the `.comment` and `.debug*` sections before it are zeroed. The program
+    /// headers, the segments and the module info are kept at their offsets, so the module loads
+    /// the same. The ELF size of the PSP header is that of the stripped ELF.
+    ///
+    /// Packing fails with [`Error::UncoveredRelocs`] if a PRX relocation section isn't inside a
+    /// relocation segment, as it would be dropped.
+    pub fn strip(mut self, strip: bool) -> Self {
+        self.strip = strip;
+        self
+    }
+
+    /// Decompress the payload right after compressing it and check that it is the input ELF,
+    /// failing with [`Error::SelfCheck`] otherwise.
+    pub fn self_check(mut self, self_check: bool) -> Self {
+        self.self_check = self_check;
+        self
+    }
+
+    /// Measure the duration of the packing phases, see [`CompPspExecutable::timings`].
+    pub fn timings(mut self, timings: bool) -> Self {
+        self.timings = timings;
+        self
+    }
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self {
+            psp_tag: None,
+            oe_tag: None,
+            level: Compression::best().level(),
+            devkit_version: None,
+            module_version: None,
+            name: None,
+            progress: None,
+            force: false,
+            preserve_reserved: false,
+            allow_no_bss: false,
+            bss_from: BssSource::Segments,
+            decrypt_mode: None,
+            seed: None,
+            no_random: false,
+            key_data: None,
+            scheck: None,
+            reserved: None,
+            comp_attribute: None,
+            kind: None,
+            overlap: false,
+            self_check: false,
+            timings: false,
+            pad_to: None,
+            strip: false,
+        }
+    }
+}
+
+/// Durations of the phases of packing, measured with [`PackOptions::timings`].
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
+pub struct PackTimings {
+    /// Parsing the executable and preparing the ELF to compress.
+    pub parse: Duration,
+    /// Compressing, or storing, the ELF.
+    pub compress: Duration,
+    /// Writing the PSP header, and the PBP sub-files after the PRX.
+    pub write: Duration,
+}
+
+/// Returns the time elapsed since `start` and restarts it, or zero if the time is not measured.
+fn lap(start: &mut Option<Instant>) -> Duration {
+    start.as_mut().map_or(Duration::ZERO, |start| {
+        let now = Instant::now();
+        let elapsed = now - *start;
+        *start = now;
+        elapsed
+    })
+}
+
+/// Statistics of a packed PSP executable.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "dev", derive(Debug, PartialEq))]
+pub struct PackStats {
+    /// Size in bytes of the input file.
+    pub original_size: usize,
+    /// Size in bytes of the packed file.
+    pub compressed_size: usize,
+    /// Size in bytes of the packed ELF, without the PSP header (and PBP sub-files).
+    pub payload_size: usize,
+    /// `compressed_size / original_size`.
+    pub ratio: f64,
+    pub kind: ExecutableKind,
+    /// Decrypt mode of the PSP header, which selects how the loader decrypts the module.
+    pub decrypt_mode: DecryptMode,
+    /// Devkit version of the PSP header, set with the decrypt mode unless given.
+    pub devkit_version: u32,
+    /// Size in bytes removed from the ELF before compressing it, `None` unless
+    /// [stripping](PackOptions::strip).
+    pub stripped_size: Option<usize>,
+}
+
 /// A compressed PSP executable with known kind.
 #[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
 pub struct CompPspExecutable {
     content: Box<[u8]>,
-    kind: ExecutableKind,
+    packed: Packed,
 }
 
 impl CompPspExecutable {
-    fn new(buf: Box<[u8]>, kind: ExecutableKind) -> Self {
-        Self { content: buf, kind }
+    fn new(buf: Box<[u8]>, packed: Packed) -> Self {
+        Self {
+            content: buf,
+            packed,
+        }
     }
 
     /// Returns the file size in bytes.
@@ -262,7 +1340,57 @@ impl CompPspExecutable {
 
     /// Returns the PSP executable kind.
     pub fn kind(&self) -> ExecutableKind {
-        self.kind
+        self.packed.kind
+    }
+
+    /// Returns the `(psp_tag, oe_tag)` written in the PSP header.
+    pub fn tags(&self) -> (u32, u32) {
+        (self.packed.header.tag, self.packed.header.oe_tag)
+    }
+
+    /// Returns the devkit version written in the PSP header.
+    pub fn devkit_version(&self) -> u32 {
+        self.packed.header.devkit_version
+    }
+
+    /// Returns the decrypt mode written in the PSP header.
+    pub fn decrypt_mode(&self) -> DecryptMode {
+        self.packed.decrypt_mode
+    }
+
+    /// Returns the PSP header written at [`header_bytes`](Self::header_bytes).
+    pub fn header(&self) -> &PspHeader {
+        &self.packed.header
+    }
+
+    /// Returns the CRC32 of the uncompressed ELF payload.
+    pub fn elf_crc32(&self) -> u32 {
+        self.packed.elf_crc32
+    }
+
+    /// Returns the raw bytes of the PSP header, at the start of the file or of the PRX of a PBP.
+    pub fn header_bytes(&self) -> &[u8] {
+        let offset = self.packed.header_offset;
+        &self.content[offset..offset + size_of::<PspHeader>()]
+    }
+
+    /// Returns the durations of the packing phases, if measured with [`PackOptions::timings`].
+    pub fn timings(&self) -> Option<PackTimings> {
+        self.packed.timings
+    }
+
+    /// Returns the sizes and header values of the packing.
+    pub fn stats(&self) -> PackStats {
+        PackStats {
+            original_size: self.packed.original_size,
+            compressed_size: self.size(),
+            payload_size: self.packed.header.comp_size as usize,
+            ratio: self.size() as f64 / self.packed.original_size as f64,
+            kind: self.packed.kind,
+            decrypt_mode: self.packed.decrypt_mode,
+            devkit_version: self.packed.header.devkit_version,
+            stripped_size: self.packed.stripped_size,
+        }
     }
 
     pub fn as_bytes(&self) -> &[u8] {
@@ -287,10 +1415,12 @@ impl AsMut<[u8]> for CompPspExecutable {
     }
 }
 
-
+/// The header of a PBP, see [`UnkPspExecutable::pbp_header`].
 #[repr(C)]
 #[cfg_attr(feature = "dev", derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct PbpHeader {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_u32"))]
     pub magic: u32,
     pub version: u32,
     pub sfo_offset: u32,
@@ -332,19 +1462,296 @@ impl TryFromBytes for PbpHeader {
 
 impl AsBytes for PbpHeader {}
 
+impl PbpHeader {
+    /// File names and offsets of the PBP sub-files, in file order.
+    fn sections(&self) -> [(&'static str, u32); 8] {
+        [
+            ("PARAM.SFO", self.sfo_offset),
+            ("ICON0.PNG", self.icon0_offset),
+            ("ICON1.PMF", self.icon1_offset),
+            ("PIC0.PNG", self.pic0_offset),
+            ("PIC1.PNG", self.pic1_offset),
+            ("SND0.AT3", self.snd0_offset),
+            ("DATA.PSP", self.prx_offset),
+            ("DATA.PSAR", self.psar_offset),
+        ]
+    }
+
+    /// Check that the sub-file offsets never decrease and that the last one is within a file of
+    /// `file_len` bytes, so that the sub-file sizes can be computed by difference.
+    ///
+    /// Fails with [`Error::MalformedPbp`] on the first offset that breaks the order.
+    fn validate(&self, file_len: usize) -> Result<(), Error> {
+        let mut previous = 0;
+        for (section, offset) in self.sections() {
+            if offset < previous {
+                return Err(Error::MalformedPbp { section, offset });
+            }
+            previous = offset;
+        }
+
+        if self.psar_offset as usize > file_len {
+            return Err(Error::MalformedPbp {
+                section: "DATA.PSAR",
+                offset: self.psar_offset,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the range of the PRX in a file of `file_len` bytes, after
+    /// [validating](Self::validate) the offsets.
+    fn prx_range(&self, file_len: usize) -> Result<Range<usize>, Error> {
+        self.validate(file_len)?;
+        Ok(self.prx_offset as usize..self.psar_offset as usize)
+    }
+}
+
+/// Builder to assemble a PBP from its sub-files.
+///
+/// Missing sub-files are zero-length, with their offset equal to the next present sub-file.
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
+pub struct PbpBuilder<'a> {
+    sfo: &'a [u8],
+    icon0: &'a [u8],
+    icon1: &'a [u8],
+    pic0: &'a [u8],
+    pic1: &'a [u8],
+    snd0: &'a [u8],
+    prx: &'a [u8],
+    psar: &'a [u8],
+}
+
+impl<'a> PbpBuilder<'a> {
+    /// Creates a builder with the required PARAM.SFO and PRX.
+    pub fn new(sfo: &'a [u8], prx: &'a [u8]) -> Self {
+        Self {
+            sfo,
+            prx,
+            ..Default::default()
+        }
+    }
+
+    pub fn icon0(mut self, icon0: &'a [u8]) -> Self {
+        self.icon0 = icon0;
+        self
+    }
+
+    pub fn icon1(mut self, icon1: &'a [u8]) -> Self {
+        self.icon1 = icon1;
+        self
+    }
+
+    pub fn pic0(mut self, pic0: &'a [u8]) -> Self {
+        self.pic0 = pic0;
+        self
+    }
+
+    pub fn pic1(mut self, pic1: &'a [u8]) -> Self {
+        self.pic1 = pic1;
+        self
+    }
+
+    pub fn snd0(mut self, snd0: &'a [u8]) -> Self {
+        self.snd0 = snd0;
+        self
+    }
+
+    pub fn psar(mut self, psar: &'a [u8]) -> Self {
+        self.psar = psar;
+        self
+    }
+
+    /// Assemble the PBP file.
+    pub fn build(&self) -> Result<Vec<u8>, Error> {
+        // Validate the required parts
+        SfoTable::from_bytes(self.sfo)?;
+        Elf32Ehdr::from_bytes(self.prx).map_err(|e| e.context("Elf32Ehdr"))?;
+
+        let parts = [
+            self.sfo, self.icon0, self.icon1, self.pic0, self.pic1, self.snd0, self.prx, self.psar,
+        ];
+
+        let mut offsets = [0u32; 8];
+        let mut offset = size_of::<PbpHeader>();
+        for (part, part_offset) in parts.iter().zip(offsets.iter_mut()) {
+            *part_offset = u32::try_from(offset).map_err(|_| Error::FileTooBig)?;
+            offset += part.len();
+        }
+
+        let [sfo_offset, icon0_offset, icon1_offset, pic0_offset, pic1_offset, snd0_offset, prx_offset, psar_offset] =
+            offsets;
+        let header = PbpHeader {
+            magic: PBP_HEADER_MAGIC,
+            version: 0x10000,
+            sfo_offset,
+            icon0_offset,
+            icon1_offset,
+            pic0_offset,
+            pic1_offset,
+            snd0_offset,
+            prx_offset,
+            psar_offset,
+        };
+
+        let mut pbp = Vec::with_capacity(offset);
+        pbp.extend_from_slice(header.as_bytes());
+        for part in parts {
+            pbp.extend_from_slice(part);
+        }
+
+        Ok(pbp)
+    }
+}
+
+/// A sub-file contained in a PBP.
+#[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
+pub struct PbpSection<'a> {
+    name: &'static str,
+    offset: usize,
+    data: &'a [u8],
+}
+
+impl<'a> PbpSection<'a> {
+    /// Returns the conventional file name of the sub-file (e.g. `ICON0.PNG`).
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the offset of the sub-file in the PBP.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the sub-file content.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+/// A section of an unpacked ELF.
+#[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
+pub struct ElfSection<'a> {
+    name: Option<&'a CStr>,
+    header: Elf32Shdr,
+}
+
+impl<'a> ElfSection<'a> {
+    /// Returns the section name, `None` if it couldn't be read from the string table.
+    pub fn name(&self) -> Option<&'a CStr> {
+        self.name
+    }
+
+    /// Returns the section header.
+    pub fn header(&self) -> &Elf32Shdr {
+        &self.header
+    }
+}
+
+/// The libraries exported and imported by a module.
+#[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
+pub struct ModuleSymbols {
+    exports: Vec<ModuleLibrary>,
+    imports: Vec<ModuleLibrary>,
+}
+
+impl ModuleSymbols {
+    /// Returns the libraries exported by the module.
+    pub fn exports(&self) -> &[ModuleLibrary] {
+        &self.exports
+    }
+
+    /// Returns the libraries imported by the module.
+    pub fn imports(&self) -> &[ModuleLibrary] {
+        &self.imports
+    }
+}
+
+/// A library exported or imported by a module, its symbols are identified by NID.
+#[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
+pub struct ModuleLibrary {
+    name: Option<String>,
+    version: u16,
+    attribute: u16,
+    functions: Vec<u32>,
+    variables: Vec<u32>,
+}
+
+impl ModuleLibrary {
+    /// Returns the library name, `None` for the unnamed system library holding the module entry
+    /// points.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns the library version.
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Returns the library attribute.
+    pub fn attribute(&self) -> u16 {
+        self.attribute
+    }
+
+    /// Returns the NIDs of the functions.
+    pub fn functions(&self) -> &[u32] {
+        &self.functions
+    }
+
+    /// Returns the NIDs of the variables.
+    pub fn variables(&self) -> &[u32] {
+        &self.variables
+    }
+}
+
+/// Common head of the library entry (export) and stub (import) tables.
+///
+/// For an entry table, `nid_table` is followed by the addresses of the symbols.
+#[repr(C)]
+struct SceLibraryTable {
+    lib_name: u32,
+    version: u16,
+    attribute: u16,
+    /// Size of the table in words.
+    len: u8,
+    var_count: u8,
+    func_count: u16,
+    nid_table: u32,
+}
+
+impl TryFromBytes for SceLibraryTable {
+    fn validate(src: &Self) -> Result<&Self, Error> {
+        Ok(src)
+    }
+}
+
+const _: () = assert!(size_of::<SceLibraryTable>() == 16);
+
+/// The module info of a PRX, see [`UnkPspExecutable::module_info`].
 #[repr(C, align(4))]
 #[cfg_attr(feature = "dev", derive(PartialEq, Eq, PartialOrd, Ord, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SceModuleInfo {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_mod_attr"))]
     pub mod_attr: ModInfoAttribute,
     pub mod_version_low: u8,
     pub mod_version_high: u8,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_name"))]
     pub mod_name: [u8; 27],
     pub terminal: u8,
-    pub gp_value: *mut c_void,
-    pub ent_top: *mut c_void,
-    pub ent_end: *mut c_void,
-    pub stub_top: *mut c_void,
-    pub stub_end: *mut c_void,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_u32"))]
+    pub gp_value: u32,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_u32"))]
+    pub ent_top: u32,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_u32"))]
+    pub ent_end: u32,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_u32"))]
+    pub stub_top: u32,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_u32"))]
+    pub stub_end: u32,
 }
 
 #[cfg(feature = "dev")]
@@ -356,15 +1763,18 @@ impl fmt::Debug for SceModuleInfo {
             .field("mod_version_high", &self.mod_version_high)
             .field("mod_name", &self.mod_name.as_bstr())
             .field("terminal", &self.terminal)
-            .field("gp_value", &self.gp_value)
-            .field("ent_top", &self.ent_top)
-            .field("ent_end", &self.ent_end)
-            .field("stub_top", &self.stub_top)
-            .field("stub_end", &self.stub_end)
+            .field("gp_value", &format_args!("0x{:08X}", self.gp_value))
+            .field("ent_top", &format_args!("0x{:08X}", self.ent_top))
+            .field("ent_end", &format_args!("0x{:08X}", self.ent_end))
+            .field("stub_top", &format_args!("0x{:08X}", self.stub_top))
+            .field("stub_end", &format_args!("0x{:08X}", self.stub_end))
             .finish()
     }
 }
 
+// The PSP is 32-bit, the layout must not depend on the host
+const _: () = assert!(size_of::<SceModuleInfo>() == 52);
+
 impl TryFromBytes for SceModuleInfo {
     fn validate(src: &Self) -> Result<&Self, Error> {
         Ok(src)
@@ -375,12 +1785,16 @@ impl AsBytes for SceModuleInfo {}
 
 #[repr(C)]
 #[cfg_attr(feature = "dev", derive(PartialEq, Eq, PartialOrd, Ord, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct PspHeader {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_u32"))]
     pub signature: u32,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_mod_attr"))]
     pub attribute: ModInfoAttribute,
     pub comp_attribute: u16,
     pub module_version_low: u8,
     pub module_version_high: u8,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_name"))]
     pub module_name: [u8; 28],
     pub version: u8,
     pub num_segments: u8,
@@ -393,19 +1807,33 @@ pub struct PspHeader {
     pub seg_addr: [u32; 4],
     pub seg_size: [u32; 4],
     pub reserved: [u32; 5],
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_u32"))]
     pub devkit_version: u32,
-    pub decrypt_mode: DecryptMode,
+    /// Raw [`DecryptMode`], see [`decryption`](Self::decryption).
+    pub decrypt_mode: u8,
     pub padding: u8,
     pub overlap_size: u16,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_bytes"))]
     pub key_data0: [u8; 0x30],
     pub comp_size: u32,
     pub _80: u32,
     pub reserved2: [u32; 2],
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_bytes"))]
     pub key_data1: [u8; 0x10],
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_u32"))]
     pub tag: u32,
+    /// Signature check block, filled when the module is signed (encrypted) by the official
+    /// tools with a key derived from the header and payload, which can't be computed here.
+    ///
+    /// The custom firmware loaders of the `~PSP` modules built by this crate never read it, so
+    /// packing leaves it zeroed unless given with [`PackOptions::scheck`].
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_bytes"))]
     pub scheck: [u8; 0x58],
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_u32"))]
     pub key_data2: u32,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_u32"))]
     pub oe_tag: u32,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::utils::serialize_hex_bytes"))]
     pub key_data3: [u8; 0x1C],
 }
 
@@ -469,7 +1897,7 @@ impl Default for PspHeader {
             seg_size: Default::default(),
             reserved: Default::default(),
             devkit_version: Default::default(),
-            decrypt_mode: Default::default(),
+            decrypt_mode: DecryptMode::default().into(),
             padding: Default::default(),
             overlap_size: Default::default(),
             key_data0: [0; 0x30],
@@ -494,7 +1922,6 @@ impl TryFromBytes for PspHeader {
 
 impl AsBytes for PspHeader {}
 
-
 #[bitflag(u16)]
 #[non_exhaustive]
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -513,9 +1940,42 @@ pub enum ModInfoAttribute {
     MsAPI  = 0x0200,
 }
 
+/// Where the BSS size of the PSP header is read from.
+#[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
+#[derive(Clone, Copy, Default)]
+pub enum BssSource {
+    /// The memory size past the file size of the loadable segments, which survives stripping.
+    ///
+    /// Falls back to the `.bss` section if the segments have no such memory, and to 0 if there
+    /// is no such section either.
+    #[default]
+    Segments,
+    /// The size of the `.bss` section, from the segments if there are no section headers.
+    Section,
+}
+
+impl BssSource {
+    /// Every BSS source.
+    pub const ALL: [BssSource; 2] = [BssSource::Segments, BssSource::Section];
+
+    /// Returns the lowercase name of the BSS source, as used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            BssSource::Segments => "segments",
+            BssSource::Section => "section",
+        }
+    }
+
+    /// Returns the BSS source with the given [`name`](Self::name).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|source| source.name() == name)
+    }
+}
+
 #[repr(u8)]
 #[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
 #[derive(Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum DecryptMode {
     Kernel = 0x2,
     Vsh    = 0x3,
@@ -527,7 +1987,200 @@ pub enum DecryptMode {
     Ms     = 0xD,
 }
 
+impl DecryptMode {
+    /// Every decrypt mode.
+    pub const ALL: [DecryptMode; 7] = [
+        DecryptMode::Kernel,
+        DecryptMode::Vsh,
+        DecryptMode::Standard,
+        DecryptMode::Updater,
+        DecryptMode::App,
+        DecryptMode::UsbWlan,
+        DecryptMode::Ms,
+    ];
+
+    /// Returns the lowercase name of the decrypt mode, as used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            DecryptMode::Kernel => "kernel",
+            DecryptMode::Vsh => "vsh",
+            DecryptMode::Standard => "standard",
+            DecryptMode::Updater => "updater",
+            DecryptMode::App => "app",
+            DecryptMode::UsbWlan => "usbwlan",
+            DecryptMode::Ms => "ms",
+        }
+    }
+
+    /// Returns the decrypt mode with the given [`name`](Self::name).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+impl fmt::Display for DecryptMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecryptMode::Kernel => f.pad("Kernel"),
+            DecryptMode::Vsh => f.pad("VSH"),
+            DecryptMode::Standard => f.pad("Standard"),
+            DecryptMode::Updater => f.pad("Updater"),
+            DecryptMode::App => f.pad("App"),
+            DecryptMode::UsbWlan => f.pad("USB/WLAN"),
+            DecryptMode::Ms => f.pad("MS"),
+        }
+    }
+}
+
+/// A multi-line report of the module, its segments and, once packed, its sizes and tags.
+impl fmt::Display for PspHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Module name:     {}", self.name())?;
+        writeln!(
+            f,
+            "Module version:  {}.{}",
+            self.module_version_high, self.module_version_low
+        )?;
+        writeln!(f, "Attributes:      0x{:04X} {:?}", self.attribute.bits(), self.attribute)?;
+        match self.decryption() {
+            Ok(mode) => writeln!(f, "Decrypt mode:    {mode} (0x{:X})", self.decrypt_mode)?,
+            Err(_) => writeln!(f, "Decrypt mode:    unknown (0x{:X})", self.decrypt_mode)?,
+        }
+        writeln!(f, "Devkit version:  0x{:08X}", self.devkit_version)?;
+        writeln!(f, "Entry:           0x{:08X}", self.entry)?;
+        writeln!(f, "BSS size:        {} B", self.bss_size)?;
+
+        // Only set once packed
+        if self.psp_size != 0 {
+            writeln!(f, "ELF size:        {} B", self.elf_size)?;
+            writeln!(f, "PSP size:        {} B", self.psp_size)?;
+            writeln!(f, "Compressed size: {} B", self.comp_size)?;
+            writeln!(f, "Tag:             0x{:08X}", self.tag)?;
+            writeln!(f, "OE tag:          0x{:08X}", self.oe_tag)?;
+        }
+
+        writeln!(f, "Segments:        {}", self.num_segments)?;
+        write!(f, "  #  Address     Size        Align")?;
+        for i in 0..(self.num_segments as usize).min(self.seg_addr.len()) {
+            write!(
+                f,
+                "\n  {i}  0x{:08X}  0x{:08X}  0x{:X}",
+                self.seg_addr[i], self.seg_size[i], self.seg_align[i]
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compression method of the payload, stored in [`PspHeader::comp_attribute`].
+///
+/// The firmware also flags KL4E and 2RLZ payloads as compressed and tells them apart from gzip by
+/// their magic, those are not produced by this packer.
+#[repr(u16)]
+#[non_exhaustive]
+#[cfg_attr(feature = "dev", derive(Debug, PartialOrd, Ord, Hash))]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CompAttribute {
+    /// The ELF is stored as is.
+    Stored = 0x0,
+    /// The ELF is gzip compressed.
+    Gzip   = 0x1,
+}
+
+impl From<CompAttribute> for u16 {
+    fn from(value: CompAttribute) -> Self {
+        value as u16
+    }
+}
+
+impl From<DecryptMode> for u8 {
+    fn from(value: DecryptMode) -> Self {
+        value as u8
+    }
+}
+
+impl TryFrom<u8> for DecryptMode {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        DecryptMode::ALL
+            .into_iter()
+            .find(|&mode| u8::from(mode) == value)
+            .ok_or(Error::Corrupted("unsupported decrypt mode"))
+    }
+}
+
+impl TryFrom<u16> for CompAttribute {
+    type Error = Error;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(CompAttribute::Stored),
+            0x1 => Ok(CompAttribute::Gzip),
+            _ => Err(Error::Corrupted("unsupported compression attribute")),
+        }
+    }
+}
+
+impl fmt::Display for CompAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompAttribute::Stored => f.pad("Stored"),
+            CompAttribute::Gzip => f.pad("Gzip"),
+        }
+    }
+}
+
 impl PspHeader {
+    /// Parse a header alone, e.g. written by `--dump-header`, only checking its `~PSP` signature.
+    pub fn from_header_bytes(src: &[u8]) -> Result<Self, Error> {
+        let header = Self::from_bytes(src).map_err(|e| e.context("PspHeader"))?;
+        if header.signature != PSP_HEADER_MAGIC {
+            return Err(Error::NotPacked);
+        }
+
+        Ok(header)
+    }
+
+    /// Parse the header at the start of an already packed PRX.
+    ///
+    /// Unlike reading the raw header, this checks the `~PSP` signature, the decrypt mode and that
+    /// `src` holds the whole compressed payload. The compression attribute is left to
+    /// [`compression`](Self::compression), as unknown values can be written on purpose with
+    /// [`PackOptions::comp_attribute`].
+    pub fn from_packed(src: &[u8]) -> Result<Self, Error> {
+        let header = Self::from_header_bytes(src)?;
+        header.decryption()?;
+
+        let payload_end = size_of::<Self>() as u64 + header.comp_size as u64;
+        if payload_end > src.len() as u64 {
+            return Err(Error::Corrupted("payload is truncated"));
+        }
+
+        Ok(header)
+    }
+
+    /// Returns the typed [`comp_attribute`](Self::comp_attribute).
+    pub fn compression(&self) -> Result<CompAttribute, Error> {
+        CompAttribute::try_from(self.comp_attribute)
+    }
+
+    /// Returns the typed [`decrypt_mode`](Self::decrypt_mode).
+    pub fn decryption(&self) -> Result<DecryptMode, Error> {
+        DecryptMode::try_from(self.decrypt_mode)
+    }
+
+    /// Returns the module name up to the first NUL byte.
+    pub fn name(&self) -> Cow<'_, str> {
+        let len = self
+            .module_name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.module_name.len());
+        String::from_utf8_lossy(&self.module_name[..len])
+    }
+
     pub fn set_decript_mode(&mut self, is_pbp: bool) {
         if self.attribute.contains(ModInfoAttribute::KernelMode) {
             if self.attribute.contains(ModInfoAttribute::BootMode) {
@@ -536,26 +2189,26 @@ impl PspHeader {
                 self.devkit_version = 0x05070110;
             }
 
-            self.decrypt_mode = DecryptMode::Kernel;
+            self.decrypt_mode = DecryptMode::Kernel.into();
         } else if is_pbp {
             if self.attribute.contains(ModInfoAttribute::VshAPI) {
-                self.decrypt_mode = DecryptMode::Updater;
+                self.decrypt_mode = DecryptMode::Updater.into();
             } else if self.attribute.contains(ModInfoAttribute::AppAPI) {
-                self.decrypt_mode = DecryptMode::App;
+                self.decrypt_mode = DecryptMode::App.into();
             } else if self.attribute.contains(ModInfoAttribute::UsbWlanAPI) {
-                self.decrypt_mode = DecryptMode::UsbWlan;
+                self.decrypt_mode = DecryptMode::UsbWlan.into();
             } else {
                 self.attribute |= ModInfoAttribute::MsAPI;
-                self.decrypt_mode = DecryptMode::Ms;
+                self.decrypt_mode = DecryptMode::Ms.into();
                 self.devkit_version = 0x06020010;
             }
         } else {
             // Standalone user PRX
             if self.attribute.contains(ModInfoAttribute::VshAPI) {
-                self.decrypt_mode = DecryptMode::Vsh;
+                self.decrypt_mode = DecryptMode::Vsh.into();
             } else {
                 self.devkit_version = 0x05070210;
-                self.decrypt_mode = DecryptMode::Standard;
+                self.decrypt_mode = DecryptMode::Standard.into();
             }
         }
     }
@@ -564,6 +2217,7 @@ impl PspHeader {
 #[repr(u8)]
 #[cfg_attr(feature = "dev", derive(Debug, PartialOrd, Ord, Hash))]
 #[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ExecutableKind {
     UserPrx,
     KernelPrx,
@@ -578,6 +2232,15 @@ impl ExecutableKind {
     pub fn is_pbp(&self) -> bool {
         matches!(self, ExecutableKind::Pbp)
     }
+
+    /// Returns the `(psp_tag, oe_tag)` used by default for this kind.
+    pub fn default_tags(&self) -> (u32, u32) {
+        KNOWN_TAGS
+            .iter()
+            .find(|&&(_, _, kind, _)| kind == *self)
+            .map(|&(psp_tag, oe_tag, ..)| (psp_tag, oe_tag))
+            .expect("every executable kind has default tags")
+    }
 }
 
 impl fmt::Display for ExecutableKind {
@@ -590,34 +2253,318 @@ impl fmt::Display for ExecutableKind {
     }
 }
 
+/// Check that the gzip `payload` decompresses to the ELF made of `elf_parts`.
+fn self_check(payload: &[u8], elf_parts: &[&[u8]]) -> Result<(), Error> {
+    let elf_len: usize = elf_parts.iter().map(|part| part.len()).sum();
+    let mut elf = Vec::with_capacity(elf_len);
+    GzDecoder::new(payload)
+        .take(elf_len as u64 + 1)
+        .read_to_end(&mut elf)
+        .map_err(|_| Error::SelfCheck)?;
+
+    if elf.len() != elf_len {
+        return Err(Error::SelfCheck);
+    }
+
+    let mut rest = elf.as_slice();
+    for part in elf_parts {
+        let (head, tail) = rest.split_at(part.len());
+        if head != *part {
+            return Err(Error::SelfCheck);
+        }
+        rest = tail;
+    }
+
+    Ok(())
+}
 
-fn default_psp_tag_handler(kind: ExecutableKind) -> u32 {
-    match kind {
-        ExecutableKind::UserPrx => 0x457B06F0,
-        ExecutableKind::KernelPrx => 0xDADADAF0,
-        ExecutableKind::Pbp => 0xADF305F0,
+/// Compute the [`PspHeader::overlap_size`] of a gzip `payload` of an ELF of `elf_size` bytes.
+///
+/// With the payload at the end of a buffer of `size` bytes and the ELF written from its start,
+/// the ELF must never get ahead of the payload bytes that are read. That is, `size` must be at
+/// least the payload size plus the biggest lead of the written bytes over the read bytes during
+/// decompression. The overlap is what the payload then shares with the ELF.
+fn overlap_size(payload: &[u8], elf_size: usize) -> Result<u16, Error> {
+    use flate2::{Decompress, FlushDecompress, Status};
+
+    /// Size of the fixed gzip header written when packing.
+    const GZIP_HEADER_SIZE: usize = 10;
+    /// Output produced between two measures of the lead.
+    const STEP_SIZE: usize = 64;
+    /// Margin for the input the decoder reads ahead and the lead between two measures.
+    const SAFETY_MARGIN: usize = 2 * STEP_SIZE;
+
+    let deflate = payload
+        .get(GZIP_HEADER_SIZE..)
+        .ok_or(Error::Corrupted("payload is truncated"))?;
+    let mut decompress = Decompress::new(false);
+    let mut out = [0u8; STEP_SIZE];
+    let mut max_lead = 0;
+
+    loop {
+        let before = (decompress.total_in(), decompress.total_out());
+        let status = decompress
+            .decompress(&deflate[before.0 as usize..], &mut out, FlushDecompress::None)
+            .map_err(|_| Error::Corrupted("invalid gzip stream"))?;
+        let after = (decompress.total_in(), decompress.total_out());
+
+        let (read, written) = (after.0 as usize, after.1 as usize);
+        max_lead = max_lead.max(written.saturating_sub(GZIP_HEADER_SIZE + read));
+
+        if status == Status::StreamEnd {
+            break;
+        } else if after == before {
+            return Err(Error::Corrupted("payload is truncated"));
+        }
     }
+
+    let buffer_size = elf_size.max(payload.len() + max_lead + SAFETY_MARGIN);
+    let overlap = (elf_size + payload.len()).saturating_sub(buffer_size);
+    Ok(u16::try_from(overlap).unwrap_or(u16::MAX))
+}
+
+/// Returns `size` for the header field `field`, failing with [`Error::SizeOverflow`] if it
+/// doesn't fit in its 32 bits.
+fn size_field(field: &'static str, size: u64) -> Result<u32, Error> {
+    u32::try_from(size).map_err(|_| Error::SizeOverflow { field, size })
 }
 
-fn default_oe_tag_handler(kind: ExecutableKind) -> u32 {
-    match kind {
-        ExecutableKind::UserPrx => 0x8555ABF2,
-        ExecutableKind::KernelPrx => 0x55668D96,
-        ExecutableKind::Pbp => 0x7316308C,
+/// Decompress the payload that follows `header` in `psp_slice`, checking it has the ELF size
+/// and, if gzip compressed, the CRC32 and size of the gzip trailer.
+fn decompress_payload(header: &PspHeader, psp_slice: &[u8]) -> Result<Vec<u8>, Error> {
+    let payload_start = size_of::<PspHeader>();
+    let payload_end = utils::add_offset(payload_start, header.comp_size)?;
+    let payload = psp_slice
+        .get(payload_start..payload_end)
+        .ok_or(Error::Corrupted("payload is truncated"))?;
+
+    let elf_size = header.elf_size as usize;
+    let elf = match header.compression()? {
+        CompAttribute::Stored => payload.to_vec(),
+        CompAttribute::Gzip => {
+            // The gzip trailer, CRC32 then ISIZE of the uncompressed data, ends the payload
+            let trailer = payload
+                .len()
+                .checked_sub(8)
+                .and_then(|start| payload.get(start..))
+                .ok_or(Error::Corrupted("payload is truncated"))?;
+            let crc32 = u32::from_le_bytes(trailer[..4].try_into().unwrap());
+            let isize = u32::from_le_bytes(trailer[4..].try_into().unwrap());
+            if isize != header.elf_size {
+                return Err(Error::Corrupted("gzip ISIZE does not match the ELF size"));
+            }
+
+            // Stop at the ELF size, so the decoder only checks the trailer of a shorter stream
+            let mut elf = Vec::with_capacity(elf_size);
+            let result = GzDecoder::new(payload).take(elf_size as u64).read_to_end(&mut elf);
+            let mut crc = Crc::new();
+            crc.update(&elf);
+
+            match result {
+                // The bytes decoded before the error are a whole stream, if they match its CRC32
+                Err(_) if crc.sum() == crc32 => {
+                    return Err(Error::Corrupted("gzip stream is shorter than the ELF size"));
+                },
+                Err(_) => return Err(Error::Corrupted("invalid gzip stream")),
+                Ok(_) if elf.len() != elf_size => {
+                    return Err(Error::Corrupted("payload is truncated"))
+                },
+                Ok(_) if crc.sum() != crc32 => {
+                    return Err(Error::Corrupted("gzip CRC32 does not match the ELF"));
+                },
+                Ok(_) => elf,
+            }
+        },
+    };
+
+    if elf.len() != elf_size {
+        return Err(Error::Corrupted("payload size does not match the ELF size"));
     }
+
+    Ok(elf)
 }
 
-fn find_module_info_phdr(exec: &[u8], elf_start: usize) -> Result<Option<Elf32Phdr>, Error> {
+/// Read the program header table of the ELF at `elf_start`.
+fn read_program_headers(exec: &[u8], elf_start: usize) -> Result<Box<[Elf32Phdr]>, Error> {
     let elf_slice = exec.get(elf_start..).ok_or(Error::FileTooSmall)?;
-    let elf_header = Elf32Ehdr::from_bytes(elf_slice)?;
-    let phdr_start_off = elf_start + elf_header.e_phoff as usize;
+    let elf_header = Elf32Ehdr::from_bytes(elf_slice).map_err(|e| e.context("Elf32Ehdr"))?;
+    let phdr_start_off = utils::add_offset(elf_start, elf_header.e_phoff)?;
     let phnum = elf_header.e_phnum as usize;
 
     let phdr_slice = exec.get(phdr_start_off..).ok_or(Error::FileTooSmall)?;
-    let phdrs = Elf32Phdr::from_bytes_with_elems(phdr_slice, phnum)?;
+    Elf32Phdr::from_bytes_with_elems(phdr_slice, phnum).map_err(|e| e.context("Elf32Phdr"))
+}
+
+fn read_section_headers(exec: &[u8], elf_start: usize) -> Result<Box<[Elf32Shdr]>, Error> {
+    let elf_slice = exec.get(elf_start..).ok_or(Error::FileTooSmall)?;
+    let elf_header = Elf32Ehdr::from_bytes(elf_slice).map_err(|e| e.context("Elf32Ehdr"))?;
+    let shdr_start_off = utils::add_offset(elf_start, elf_header.e_shoff)?;
+    let shnum = elf_header.e_shnum as usize;
+
+    let shdr_slice = exec.get(shdr_start_off..).ok_or(Error::FileTooSmall)?;
+    Elf32Shdr::from_bytes_with_elems(shdr_slice, shnum).map_err(|e| e.context("Elf32Shdr"))
+}
+
+/// Translate a virtual address to an offset in `exec`, in the file part of a loadable segment.
+fn vaddr_offset(phdrs: &[Elf32Phdr], elf_start: usize, vaddr: u32) -> Option<usize> {
+    let phdr = phdrs.iter().find(|phdr| {
+        phdr.is_load() && vaddr >= phdr.p_vaddr && vaddr - phdr.p_vaddr < phdr.p_filesz
+    })?;
+    let segment_start = utils::add_offset(elf_start, phdr.p_offset).ok()?;
+    utils::add_offset(segment_start, vaddr - phdr.p_vaddr).ok()
+}
+
+/// Read the library tables between the virtual addresses of `range`.
+fn read_libraries(
+    exec: &[u8], elf_start: usize, phdrs: &[Elf32Phdr], range: Range<u32>,
+) -> Result<Vec<ModuleLibrary>, Error> {
+    let mut libraries = Vec::new();
+    let mut addr = range.start;
+    while addr < range.end {
+        let table_start =
+            vaddr_offset(phdrs, elf_start, addr).ok_or(Error::BadLibraryTable(addr))?;
+        let table_slice = exec.get(table_start..).ok_or(Error::FileTooSmall)?;
+        let table =
+            SceLibraryTable::from_bytes(table_slice).map_err(|e| e.context("SceLibraryTable"))?;
+        // Also makes sure to always move forward
+        if (table.len as usize) < size_of::<SceLibraryTable>() / 4 {
+            return Err(Error::BadLibraryTable(addr));
+        }
+
+        let name = match table.lib_name {
+            0 => None,
+            lib_name => {
+                let name_start =
+                    vaddr_offset(phdrs, elf_start, lib_name).ok_or(Error::BadLibraryTable(addr))?;
+                let name_slice = exec.get(name_start..).ok_or(Error::FileTooSmall)?;
+                let name = CStr::from_bytes_until_nul(name_slice)
+                    .map_err(|e| Error::from(e).context("library name"))?;
+                Some(name.to_string_lossy().into_owned())
+            },
+        };
+
+        let func_count = table.func_count as usize;
+        let nid_count = func_count + table.var_count as usize;
+        let mut nids = Vec::with_capacity(nid_count);
+        if nid_count > 0 {
+            let nids_start = vaddr_offset(phdrs, elf_start, table.nid_table)
+                .ok_or(Error::BadLibraryTable(addr))?;
+            let nids_slice = exec
+                .get(nids_start..nids_start + nid_count * 4)
+                .ok_or(Error::FileTooSmall)?;
+            nids.extend(
+                nids_slice
+                    .chunks_exact(4)
+                    .map(|nid| u32::from_le_bytes(nid.try_into().unwrap())),
+            );
+        }
+        let variables = nids.split_off(func_count);
+
+        libraries.push(ModuleLibrary {
+            name,
+            version: table.version,
+            attribute: table.attribute,
+            functions: nids,
+            variables,
+        });
+
+        match addr.checked_add(table.len as u32 * 4) {
+            Some(next) => addr = next,
+            None => break,
+        }
+    }
 
-    for phdr in phdrs {
-        if phdr.p_type == 1 && phdr.p_vaddr != phdr.p_paddr {
+    Ok(libraries)
+}
+
+/// Read the section headers with their names, which are `None` if the section name string table
+/// is missing or malformed.
+fn read_named_sections(exec: &[u8], elf_start: usize) -> Result<Vec<ElfSection<'_>>, Error> {
+    let elf_slice = exec.get(elf_start..).ok_or(Error::FileTooSmall)?;
+    let elf_header = Elf32Ehdr::from_bytes(elf_slice).map_err(|e| e.context("Elf32Ehdr"))?;
+    let shdrs = read_section_headers(exec, elf_start)?;
+
+    // `SHN_UNDEF` means there is no string table at all
+    let strtab = match elf_header.e_shstrndx {
+        0 => None,
+        _ => shstrtab(&elf_header, &shdrs).ok().and_then(|strtab| {
+            let start = utils::add_offset(elf_start, strtab.sh_offset).ok()?;
+            let end = utils::add_offset(start, strtab.sh_size).ok()?;
+            exec.get(start..end)
+        }),
+    };
+
+    Ok(Vec::from(shdrs)
+        .into_iter()
+        .map(|header| {
+            let name = strtab
+                .and_then(|strtab| strtab.get(header.sh_name as usize..))
+                .and_then(|name| CStr::from_bytes_until_nul(name).ok());
+            ElfSection { name, header }
+        })
+        .collect())
+}
+
+/// Copy `elf` without what the loader doesn't need: the section header table, everything after
+/// the last segment, and the `.comment` and `.debug*` sections, zeroed if they come before it.
+///
+/// The ELF and program headers, the content of the segments and the module info at
+/// `mod_info_range` are kept as is, at the same offsets. Fails with
+/// [`Error::UncoveredRelocs`] if a PRX relocation section is outside the relocation segments, as
+/// the loader would only find it through the dropped section header table.
+fn strip_elf(elf: &[u8], mod_info_range: Range<usize>) -> Result<Vec<u8>, Error> {
+    let mut elf_header = Elf32Ehdr::from_bytes(elf).map_err(|e| e.context("Elf32Ehdr"))?;
+    let phdrs = read_program_headers(elf, 0)?;
+
+    let phdrs_start = elf_header.e_phoff as usize;
+    let mut kept = vec![
+        0..size_of::<Elf32Ehdr>(),
+        phdrs_start..utils::add_offset(phdrs_start, size_of_val(&*phdrs) as u32)?,
+        mod_info_range,
+    ];
+    let mut relocs = Vec::new();
+    for phdr in &phdrs {
+        let start = phdr.p_offset as usize;
+        let range = start..utils::add_offset(start, phdr.p_filesz)?;
+        if phdr.is_reloc() {
+            relocs.push(range.clone());
+        }
+        kept.push(range);
+    }
+    let kept_end = kept.iter().map(|range| range.end).max().unwrap_or(0);
+    let mut stripped = elf.get(..kept_end).ok_or(Error::FileTooSmall)?.to_vec();
+
+    for section in read_named_sections(elf, 0)? {
+        let shdr = section.header();
+        let is_debug = section.name().is_some_and(|name| {
+            let name = name.to_bytes();
+            name == b".comment" || name.starts_with(b".debug")
+        });
+        let start = shdr.sh_offset as usize;
+        let end = start.saturating_add(shdr.sh_size as usize);
+        if shdr.is_reloc() && !relocs.iter().any(|range| range.start <= start && end <= range.end) {
+            return Err(Error::UncoveredRelocs(shdr.sh_offset));
+        }
+        // Never touch anything a segment needs, even if the sections say otherwise
+        if !is_debug || shdr.is_nobits() || end > kept_end {
+            continue;
+        }
+        if kept.iter().all(|range| end <= range.start || range.end <= start) {
+            stripped[start..end].fill(0);
+        }
+    }
+
+    elf_header.e_shoff = 0;
+    elf_header.e_shnum = 0;
+    elf_header.e_shstrndx = 0;
+    stripped[..size_of::<Elf32Ehdr>()].copy_from_slice(elf_header.as_bytes());
+
+    Ok(stripped)
+}
+
+fn find_module_info_phdr(exec: &[u8], elf_start: usize) -> Result<Option<Elf32Phdr>, Error> {
+    for phdr in read_program_headers(exec, elf_start)? {
+        if phdr.is_load() && phdr.p_vaddr != phdr.p_paddr {
             // Found module info
             return Ok(Some(phdr.clone()));
         }
@@ -627,61 +2574,93 @@ fn find_module_info_phdr(exec: &[u8], elf_start: usize) -> Result<Option<Elf32Ph
 }
 
 fn read_segments_bss_info(
-    exec: &[u8], elf_start: usize, psp_header: &mut PspHeader,
+    exec: &[u8], elf_start: usize, bss_from: BssSource, psp_header: &mut PspHeader,
 ) -> Result<(), Error> {
     let elf_slice = exec.get(elf_start..).ok_or(Error::FileTooSmall)?;
-    let elf_header = Elf32Ehdr::from_bytes(elf_slice)?;
-
-    let phdr_start_off = elf_start + elf_header.e_phoff as usize;
-    let phnum = psp_header.num_segments as usize;
-
-    let phdr_slice = exec.get(phdr_start_off..).ok_or(Error::FileTooSmall)?;
-    let phdrs = Elf32Phdr::from_bytes_with_elems(phdr_slice, phnum)?;
-
-    for (i, phdr) in phdrs.iter().enumerate() {
-        psp_header.seg_align[i] = phdr.p_align as u16;
+    let elf_header = Elf32Ehdr::from_bytes(elf_slice).map_err(|e| e.context("Elf32Ehdr"))?;
+    let phdrs = read_program_headers(exec, elf_start)?;
+
+    // Only the loadable segments are described in the PSP header, and count towards its limit:
+    // the loader applies the PRX relocations from the packed ELF, and needs nothing else
+    let load_phdrs: Vec<_> = phdrs.into_iter().filter(Elf32Phdr::is_load).collect();
+    psp_header.num_segments = match load_phdrs.len() {
+        0 => return Err(Error::NoSegments),
+        x if x > 4 => return Err(Error::TooManySegments(x)),
+        x => x as u8,
+    };
+
+    for (i, phdr) in load_phdrs.iter().enumerate() {
+        psp_header.seg_align[i] =
+            u16::try_from(phdr.p_align).map_err(|_| Error::SegmentAlign(phdr.p_align))?;
         psp_header.seg_addr[i] = phdr.p_vaddr;
         psp_header.seg_size[i] = phdr.p_memsz;
     }
 
-    let shdr_start_off = elf_start + elf_header.e_shoff as usize;
-    let shnum = elf_header.e_shnum as usize;
-    let shdr_slice = exec.get(shdr_start_off..).ok_or(Error::FileTooSmall)?;
-    let shdrs = Elf32Shdr::from_bytes_with_elems(shdr_slice, shnum)?;
+    // The BSS is the part of the segments in memory but not in the file
+    let segments_bss = load_phdrs
+        .iter()
+        .map(|phdr| phdr.p_memsz.saturating_sub(phdr.p_filesz))
+        .fold(0, u32::saturating_add);
+    // Without section headers, e.g. a stripped PRX, the segments are all there is
+    let from_segments = match bss_from {
+        BssSource::Segments => segments_bss != 0,
+        BssSource::Section => false,
+    };
+    if from_segments || elf_header.e_shnum == 0 {
+        psp_header.bss_size = segments_bss;
+        return Ok(());
+    }
+
+    let shdrs = read_section_headers(exec, elf_start)?;
 
-    let strtab_offset = elf_start + shdrs[elf_header.e_shstrndx as usize].sh_offset as usize;
+    let strtab_offset = utils::add_offset(elf_start, shstrtab(&elf_header, &shdrs)?.sh_offset)?;
 
     for shdr in shdrs {
-        let name_start = strtab_offset + shdr.sh_name as usize;
-        let name_end = name_start + 4;
-        let name = exec.get(name_start..name_end).ok_or(Error::FileTooSmall)?;
-        if name == b".bss" {
+        if section_name(exec, strtab_offset, shdr.sh_name)? == c".bss" {
             psp_header.bss_size = shdr.sh_size;
             return Ok(());
         }
     }
 
-    Err(Error::BssNotFound)
+    match bss_from {
+        // Neither the segments nor the sections have a BSS, so there is none
+        BssSource::Segments => {
+            psp_header.bss_size = 0;
+            Ok(())
+        },
+        BssSource::Section => Err(Error::BssNotFound),
+    }
+}
+
+/// Returns the header of the section name string table, failing with [`Error::NotElf`] if
+/// `e_shstrndx` is out of the section header table.
+fn shstrtab<'a>(elf_header: &Elf32Ehdr, shdrs: &'a [Elf32Shdr]) -> Result<&'a Elf32Shdr, Error> {
+    shdrs.get(elf_header.e_shstrndx as usize).ok_or(Error::NotElf)
+}
+
+/// Read the full name of a section from the section header string table at `strtab_offset`.
+fn section_name(exec: &[u8], strtab_offset: usize, sh_name: u32) -> Result<&CStr, Error> {
+    let name = exec
+        .get(utils::add_offset(strtab_offset, sh_name)?..)
+        .ok_or(Error::FileTooSmall)?;
+    CStr::from_bytes_until_nul(name).map_err(|e| Error::from(e).context("section name"))
 }
 
 fn find_segment(
     exec: &[u8], elf_start: usize, seg_name: &CStr,
 ) -> Result<Option<Elf32Shdr>, Error> {
     let elf_slice = exec.get(elf_start..).ok_or(Error::FileTooSmall)?;
-    let elf_header = Elf32Ehdr::from_bytes(elf_slice)?;
+    let elf_header = Elf32Ehdr::from_bytes(elf_slice).map_err(|e| e.context("Elf32Ehdr"))?;
+    if elf_header.e_shnum == 0 {
+        return Ok(None);
+    }
 
-    let shdr_start_off = elf_start + elf_header.e_shoff as usize;
-    let shnum = elf_header.e_shnum as usize;
-    let shdr_slice = exec.get(shdr_start_off..).ok_or(Error::FileTooSmall)?;
-    let shdrs = Elf32Shdr::from_bytes_with_elems(shdr_slice, shnum)?;
+    let shdrs = read_section_headers(exec, elf_start)?;
 
-    let strtab_offset = elf_start + shdrs[elf_header.e_shstrndx as usize].sh_offset as usize;
+    let strtab_offset = utils::add_offset(elf_start, shstrtab(&elf_header, &shdrs)?.sh_offset)?;
 
     for shdr in shdrs {
-        let name_start = strtab_offset + shdr.sh_name as usize;
-        let name = exec.get(name_start..).ok_or(Error::BssNotFound)?;
-        let name = CStr::from_bytes_until_nul(name)?;
-        if name == seg_name {
+        if section_name(exec, strtab_offset, shdr.sh_name)? == seg_name {
             return Ok(Some(shdr));
         }
     }
@@ -0,0 +1,164 @@
+use std::{ffi::CStr, fmt};
+
+use crate::{error::Error, utils::TryFromBytes};
+
+const SFO_MAGIC: u32 = 0x46535000;
+
+const SFO_FMT_UTF8S: u16 = 0x0004;
+const SFO_FMT_UTF8: u16 = 0x0204;
+const SFO_FMT_INT32: u16 = 0x0404;
+
+#[repr(C)]
+#[derive(Clone)]
+#[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
+struct SfoHeader {
+    magic: u32,
+    version: u32,
+    key_table_start: u32,
+    data_table_start: u32,
+    tables_entries: u32,
+}
+
+impl TryFromBytes for SfoHeader {
+    fn validate(src: &Self) -> Result<&Self, Error> {
+        if src.magic != SFO_MAGIC {
+            return Err(Error::NotSfo);
+        }
+        Ok(src)
+    }
+}
+
+#[repr(C)]
+#[derive(Clone)]
+#[cfg_attr(feature = "dev", derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash))]
+struct SfoIndexEntry {
+    key_offset: u16,
+    data_fmt: u16,
+    data_len: u32,
+    data_max_len: u32,
+    data_offset: u32,
+}
+
+impl TryFromBytes for SfoIndexEntry {
+    fn validate(src: &Self) -> Result<&Self, Error> {
+        Ok(src)
+    }
+}
+
+/// A value of a PARAM.SFO entry.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "dev", derive(Debug, PartialOrd, Ord, Hash))]
+pub enum SfoValue {
+    /// UTF-8 string that is not NUL terminated.
+    Utf8S(String),
+    /// NUL terminated UTF-8 string.
+    Utf8(String),
+    /// 32 bits unsigned integer.
+    Int32(u32),
+}
+
+impl SfoValue {
+    /// Returns the value as a string slice, if it is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            SfoValue::Utf8S(s) | SfoValue::Utf8(s) => Some(s),
+            SfoValue::Int32(_) => None,
+        }
+    }
+
+    /// Returns the value as an integer, if it is an integer.
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            SfoValue::Int32(x) => Some(*x),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SfoValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SfoValue::Utf8S(s) | SfoValue::Utf8(s) => f.pad(s),
+            SfoValue::Int32(x) => write!(f, "0x{x:08X}"),
+        }
+    }
+}
+
+/// The key/value table of a PARAM.SFO file.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "dev", derive(Debug, PartialOrd, Ord, Hash))]
+pub struct SfoTable {
+    entries: Vec<(String, SfoValue)>,
+}
+
+impl SfoTable {
+    /// Parse a PARAM.SFO from its bytes.
+    pub fn from_bytes(src: &[u8]) -> Result<Self, Error> {
+        if src.len() < size_of::<SfoHeader>() {
+            return Err(Error::SfoTruncated("header"));
+        }
+        let header = SfoHeader::from_bytes(src).map_err(|e| e.context("SfoHeader"))?;
+        let count = header.tables_entries as usize;
+
+        let index_slice = src.get(size_of::<SfoHeader>()..).unwrap_or_default();
+        if index_slice.len() / size_of::<SfoIndexEntry>() < count {
+            return Err(Error::SfoTruncated("index table"));
+        }
+        let index = SfoIndexEntry::from_bytes_with_elems(index_slice, count)
+            .map_err(|e| e.context("SfoIndexEntry"))?;
+
+        let key_table = src
+            .get(header.key_table_start as usize..)
+            .ok_or(Error::SfoTruncated("key table"))?;
+        let data_table = src
+            .get(header.data_table_start as usize..)
+            .ok_or(Error::SfoTruncated("data table"))?;
+
+        let mut entries = Vec::with_capacity(count);
+        for entry in index {
+            let key = key_table
+                .get(entry.key_offset as usize..)
+                .and_then(|key| CStr::from_bytes_until_nul(key).ok())
+                .ok_or(Error::SfoTruncated("key table"))?;
+            let key = key.to_string_lossy().into_owned();
+
+            let data_start = entry.data_offset as usize;
+            let data = data_table
+                .get(data_start..)
+                .and_then(|data| data.get(..entry.data_len as usize))
+                .ok_or(Error::SfoTruncated("data table"))?;
+
+            let value = match entry.data_fmt {
+                SFO_FMT_UTF8S => SfoValue::Utf8S(String::from_utf8_lossy(data).into_owned()),
+                SFO_FMT_UTF8 => {
+                    let data = CStr::from_bytes_until_nul(data).map(CStr::to_bytes).unwrap_or(data);
+                    SfoValue::Utf8(String::from_utf8_lossy(data).into_owned())
+                },
+                SFO_FMT_INT32 => {
+                    let data = data.try_into().map_err(|_| Error::SfoTruncated("data table"))?;
+                    SfoValue::Int32(u32::from_le_bytes(data))
+                },
+                _ => return Err(Error::NotSfo),
+            };
+
+            entries.push((key, value));
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Returns the value of the `key` entry.
+    pub fn get(&self, key: &str) -> Option<&SfoValue> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// Returns the string value of the `key` entry.
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(SfoValue::as_str)
+    }
+
+    /// Iterate over all entries, in the file order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &SfoValue)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v))
+    }
+}
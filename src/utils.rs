@@ -1,12 +1,138 @@
 #![allow(unused)]
-use std::slice;
+use std::{io, slice};
 
 use crate::error::Error;
 
+/// Upper bound of the gzip compressed size of `len_src` bytes, reached by incompressible data.
+///
+/// This is `deflateBound` of zlib, with the 18 bytes of the gzip header and trailer instead of
+/// the 6 of the zlib ones.
 pub fn gzip_max_compressed_size(len_src: usize) -> usize {
-    let num_16k_block = len_src.div_ceil(16384);
+    len_src + (len_src >> 12) + (len_src >> 14) + (len_src >> 25) + 13 - 6 + 18
+}
+
+/// Returns `base + offset`, an offset read from a header, failing with [`Error::FileTooSmall`] on
+/// overflow as no file can be that big.
+pub fn add_offset(base: usize, offset: u32) -> Result<usize, Error> {
+    base.checked_add(offset as usize).ok_or(Error::FileTooSmall)
+}
+
+/// Copy `name` into `dst`, truncated on a char boundary so that at least one NUL byte remains,
+/// and zero the rest of `dst`.
+pub fn copy_name(dst: &mut [u8], name: &str) {
+    let max_len = dst.len().saturating_sub(1);
+    let len = (0..=max_len.min(name.len()))
+        .rev()
+        .find(|&i| name.is_char_boundary(i))
+        .unwrap_or(0);
+
+    dst.fill(0);
+    dst[..len].copy_from_slice(&name.as_bytes()[..len]);
+}
+
+/// Serialize a `u32` as a `0x` prefixed hex string.
+#[cfg(feature = "serde")]
+pub fn serialize_hex_u32<S: serde::Serializer>(
+    value: &u32, serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("0x{value:08X}"))
+}
+
+/// Serialize a byte array as a hex string.
+#[cfg(feature = "serde")]
+pub fn serialize_hex_bytes<S: serde::Serializer, const N: usize>(
+    value: &[u8; N], serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let hex: String = value.iter().map(|b| format!("{b:02X}")).collect();
+    serializer.serialize_str(&hex)
+}
+
+/// Serialize a NUL padded name as a string.
+#[cfg(feature = "serde")]
+pub fn serialize_name<S: serde::Serializer, const N: usize>(
+    value: &[u8; N], serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let len = value.iter().position(|&b| b == 0).unwrap_or(N);
+    serializer.serialize_str(&String::from_utf8_lossy(&value[..len]))
+}
+
+/// Serialize the module attributes as their bits.
+#[cfg(feature = "serde")]
+pub fn serialize_mod_attr<S: serde::Serializer>(
+    value: &crate::psp::ModInfoAttribute, serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u16(value.bits())
+}
+
+/// A [`Write`](io::Write) adapter that reports the number of bytes written through it.
+pub struct ProgressWriter<W> {
+    inner: W,
+    written: usize,
+    total: usize,
+    progress: fn(usize, usize),
+}
+
+impl<W: io::Write> ProgressWriter<W> {
+    pub fn new(inner: W, total: usize, progress: fn(usize, usize)) -> Self {
+        Self {
+            inner,
+            written: 0,
+            total,
+            progress,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: io::Write> io::Write for ProgressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.written += len;
+        (self.progress)(self.written, self.total);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
 
-    num_16k_block + 6 + (num_16k_block * 5) + 18
+/// A [`Write`](io::Write) adapter that also keeps a copy of the bytes written through it, if
+/// enabled.
+pub struct CopyWriter<W> {
+    inner: W,
+    copy: Option<Vec<u8>>,
+}
+
+impl<W: io::Write> CopyWriter<W> {
+    pub fn new(inner: W, enabled: bool) -> Self {
+        Self {
+            inner,
+            copy: enabled.then(Vec::new),
+        }
+    }
+
+    /// Returns the copy of the written bytes, `None` if not enabled.
+    pub fn into_copy(self) -> Option<Vec<u8>> {
+        self.copy
+    }
+}
+
+impl<W: io::Write> io::Write for CopyWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        if let Some(copy) = &mut self.copy {
+            copy.extend_from_slice(&buf[..len]);
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
 }
 
 #[track_caller]
@@ -19,8 +145,12 @@ pub fn rand() -> rapidhash::RapidRng {
     rand::SeedableRng::from_seed(seed)
 }
 
+/// A random generator that always produces the same values for the same `seed`.
+pub fn rand_from_seed(seed: u64) -> rapidhash::RapidRng {
+    rand::SeedableRng::seed_from_u64(seed)
+}
 
-pub trait TryFromBytes: Sized {
+pub(crate) trait TryFromBytes: Sized {
     fn validate(src: &Self) -> Result<&Self, Error>;
 
     #[doc(hidden)]
@@ -54,7 +184,7 @@ pub trait TryFromBytes: Sized {
     #[inline]
     #[must_use = "has no side effects"]
     fn from_bytes_with_elems(src: &[u8], count: usize) -> Result<Box<[Self]>, Error> {
-        let expected_len = size_of::<Self>() * count;
+        let expected_len = size_of::<Self>().checked_mul(count).ok_or(Error::FileTooSmall)?;
         split_bytes(src, expected_len).and_then(|(head, _rest)| {
             let mut vec = Vec::with_capacity(count);
 
@@ -74,7 +204,6 @@ pub trait TryFromBytes: Sized {
         })
     }
 
-
     #[inline]
     #[must_use = "has no side effects"]
     fn ref_from_bytes(src: &[u8]) -> Result<&Self, Error> {
@@ -86,6 +215,7 @@ pub trait TryFromBytes: Sized {
                 return Err(Error::Alignment {
                     align: align_of::<Self>(),
                     addr: ptr.addr(),
+                    context: None,
                 });
             }
 
@@ -99,7 +229,7 @@ pub trait TryFromBytes: Sized {
     #[inline]
     #[must_use = "has no side effects"]
     fn ref_from_bytes_with_elems(src: &[u8], count: usize) -> Result<&[Self], Error> {
-        let expected_len = size_of::<Self>() * count;
+        let expected_len = size_of::<Self>().checked_mul(count).ok_or(Error::FileTooSmall)?;
 
         split_bytes(src, expected_len).and_then(|(head, _rest)| {
             let ptr = head.as_ptr().cast::<Self>();
@@ -107,6 +237,7 @@ pub trait TryFromBytes: Sized {
                 return Err(Error::Alignment {
                     align: align_of::<Self>(),
                     addr: ptr.addr(),
+                    context: None,
                 });
             }
 
@@ -131,6 +262,7 @@ pub trait TryFromBytes: Sized {
                 return Err(Error::Alignment {
                     align: align_of::<Self>(),
                     addr: ptr.addr(),
+                    context: None,
                 });
             }
             // Safety: this cast and dereference are made sound by the length
@@ -144,7 +276,7 @@ pub trait TryFromBytes: Sized {
     #[inline]
     #[must_use = "has no side effects"]
     fn mut_from_bytes_with_elems(src: &mut [u8], count: usize) -> Result<&mut [Self], Error> {
-        let expected_len = size_of::<Self>() * count;
+        let expected_len = size_of::<Self>().checked_mul(count).ok_or(Error::FileTooSmall)?;
 
         split_mut_bytes(src, expected_len).and_then(|(head, _rest)| {
             let ptr = head.as_mut_ptr().cast::<Self>();
@@ -152,6 +284,7 @@ pub trait TryFromBytes: Sized {
                 return Err(Error::Alignment {
                     align: align_of::<Self>(),
                     addr: ptr.addr(),
+                    context: None,
                 });
             }
             // Safety: this cast and dereference are made sound by the length
@@ -165,7 +298,7 @@ pub trait TryFromBytes: Sized {
     }
 }
 
-pub trait AsBytes: Sized {
+pub(crate) trait AsBytes: Sized {
     #[inline]
     #[must_use = "has no side effects"]
     fn as_bytes(&self) -> &[u8] {
@@ -220,6 +353,7 @@ fn split_bytes(bytes: &[u8], expected_byte_len: usize) -> Result<(&[u8], &[u8]),
     bytes.split_at_checked(expected_byte_len).ok_or(Error::FromBytes {
         input_len: bytes.len(),
         expected_len: Some(expected_byte_len),
+        context: None,
     })
 }
 
@@ -230,5 +364,6 @@ fn split_mut_bytes(
     bytes.split_at_mut_checked(expected_byte_len).ok_or(Error::FromBytes {
         input_len: len,
         expected_len: Some(expected_byte_len),
+        context: None,
     })
 }
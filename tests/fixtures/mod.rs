@@ -0,0 +1,386 @@
+//! Synthetic PSP executables built in memory for the integration tests.
+#![allow(dead_code)]
+
+const ELF_HEADER_SIZE: usize = 52;
+const PHDR_SIZE: usize = 32;
+const SHDR_SIZE: usize = 40;
+const MOD_INFO_SIZE: usize = 52;
+const EXTRA_SEGMENT_SIZE: usize = 256;
+
+const ELF_TYPE_PRX: u16 = 0xFFA0;
+const EM_MIPS: u16 = 8;
+const PT_LOAD: u32 = 1;
+const PT_PRX_RELOC2: u32 = 0x700000A1;
+const SHT_PRX_RELOC: u32 = 0x700000A0;
+const SHT_PROGBITS: u32 = 1;
+const SHT_STRTAB: u32 = 3;
+const SHT_NOBITS: u32 = 8;
+
+/// Builder of a minimal PRX: an ELF header, one `PT_LOAD` program header by default, a
+/// `.rodata.sceModuleInfo` section holding the `SceModuleInfo` at the start of the segment, a
+/// `.bss` section, optionally preceded by a decoy one, and a `.shstrtab` section.
+#[derive(Clone)]
+pub struct Prx {
+    kernel: bool,
+    machine: u16,
+    name: &'static str,
+    version: (u8, u8),
+    align: u32,
+    payload_len: usize,
+    random_payload: bool,
+    bss_name: &'static str,
+    bss_size: u32,
+    decoy_bss: &'static str,
+    shstrndx: Option<u16>,
+    load_segments: u16,
+    reloc_segments: u16,
+    reloc_section: bool,
+    sections: bool,
+    library_tables: bool,
+    comment: &'static str,
+}
+
+impl Prx {
+    /// A user mode PRX.
+    pub fn user() -> Self {
+        Self {
+            kernel: false,
+            machine: EM_MIPS,
+            name: "test_module",
+            version: (1, 2),
+            align: 16,
+            payload_len: 4096,
+            random_payload: false,
+            bss_name: ".bss",
+            bss_size: 256,
+            decoy_bss: "",
+            shstrndx: None,
+            load_segments: 1,
+            reloc_segments: 0,
+            reloc_section: false,
+            sections: true,
+            library_tables: false,
+            comment: "",
+        }
+    }
+
+    /// A kernel mode PRX, with the kernel mode attribute and the high bit of the module info
+    /// `p_paddr` set.
+    pub fn kernel() -> Self {
+        Self {
+            kernel: true,
+            ..Self::user()
+        }
+    }
+
+    /// The ELF `e_machine`, MIPS by default.
+    pub fn machine(mut self, machine: u16) -> Self {
+        self.machine = machine;
+        self
+    }
+
+    pub fn name(mut self, name: &'static str) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// The module version, as `(major, minor)`.
+    pub fn version(mut self, major: u8, minor: u8) -> Self {
+        self.version = (major, minor);
+        self
+    }
+
+    /// The `p_align` of the segment.
+    pub fn align(mut self, align: u32) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Size of the data after the `SceModuleInfo` in the segment.
+    pub fn payload_len(mut self, payload_len: usize) -> Self {
+        self.payload_len = payload_len;
+        self
+    }
+
+    /// Fill the data after the `SceModuleInfo` with incompressible pseudo-random bytes.
+    pub fn random_payload(mut self, random_payload: bool) -> Self {
+        self.random_payload = random_payload;
+        self
+    }
+
+    /// Name of the BSS section, anything but `.bss` makes it missing.
+    pub fn bss_name(mut self, bss_name: &'static str) -> Self {
+        self.bss_name = bss_name;
+        self
+    }
+
+    pub fn bss_size(mut self, bss_size: u32) -> Self {
+        self.bss_size = bss_size;
+        self
+    }
+
+    /// Add a `SHT_NOBITS` section of 64 bytes with the given name right before the BSS
+    /// section, in the section headers and in the section names.
+    pub fn decoy_bss(mut self, decoy_bss: &'static str) -> Self {
+        self.decoy_bss = decoy_bss;
+        self
+    }
+
+    /// Index of the section names section, the one after the BSS section by default.
+    pub fn shstrndx(mut self, shstrndx: u16) -> Self {
+        self.shstrndx = Some(shstrndx);
+        self
+    }
+
+    /// Number of `PT_LOAD` program headers. The first one holds the module info, each other one
+    /// a segment of 256 bytes at virtual address `0x10000 * i`, after the first one in the file.
+    pub fn load_segments(mut self, load_segments: u16) -> Self {
+        self.load_segments = load_segments;
+        self
+    }
+
+    /// Number of empty `0x700000A1` relocation program headers after the `PT_LOAD` ones.
+    pub fn reloc_segments(mut self, reloc_segments: u16) -> Self {
+        self.reloc_segments = reloc_segments;
+        self
+    }
+
+    /// Add 8 bytes of relocations after the section names, in a `0x700000A0` section that is
+    /// the last one, and covered by the relocation program headers if any.
+    pub fn reloc_section(mut self, reloc_section: bool) -> Self {
+        self.reloc_section = reloc_section;
+        self
+    }
+
+    /// Whether to write the section headers, stripped (`e_shnum` of 0) otherwise.
+    pub fn sections(mut self, sections: bool) -> Self {
+        self.sections = sections;
+        self
+    }
+
+    /// Write library tables at the start of the payload: the system library exporting the
+    /// `0xD632ACDB` function and the `0xF01D73A7` variable, and `sceTest` importing the
+    /// `0x11111111` and `0x22222222` functions.
+    pub fn library_tables(mut self, library_tables: bool) -> Self {
+        self.library_tables = library_tables;
+        self
+    }
+
+    /// Add a `.comment` section with the given content between the program headers and the
+    /// segment, as the last section.
+    pub fn comment(mut self, comment: &'static str) -> Self {
+        self.comment = comment;
+        self
+    }
+
+    /// Offset of the `.comment` section in the file, right after the program headers.
+    pub fn comment_offset(&self) -> usize {
+        ELF_HEADER_SIZE + PHDR_SIZE * (self.load_segments + self.reloc_segments) as usize
+    }
+
+    /// Offset of the segment, and of the `SceModuleInfo`, in the file.
+    pub fn segment_offset(&self) -> usize {
+        (self.comment_offset() + self.comment.len()).next_multiple_of(16)
+    }
+
+    /// Size of the segment in the file.
+    pub fn segment_size(&self) -> usize {
+        MOD_INFO_SIZE + self.payload_len
+    }
+
+    /// Offset of the `i`th extra loadable segment in the file, from 1.
+    pub fn extra_segment_offset(&self, i: u16) -> usize {
+        self.segment_offset() + self.segment_size() + EXTRA_SEGMENT_SIZE * (i as usize - 1)
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let segment_offset = self.segment_offset();
+        let segment_size = self.segment_size();
+
+        let decoy = !self.decoy_bss.is_empty();
+        let decoy_name = if decoy {
+            [self.decoy_bss.as_bytes(), b"\0"].concat()
+        } else {
+            vec![]
+        };
+        let reloc_name: &[u8] = if self.reloc_section { b".rel.text\0" } else { b"" };
+        let shstrtab = [
+            b"\0.rodata.sceModuleInfo\0",
+            decoy_name.as_slice(),
+            self.bss_name.as_bytes(),
+            b"\0.shstrtab\0.comment\0",
+            reloc_name,
+        ]
+        .concat();
+        let mod_info_name = 1;
+        let decoy_bss_name = mod_info_name + ".rodata.sceModuleInfo".len() as u32 + 1;
+        let bss_name = decoy_bss_name + decoy_name.len() as u32;
+        let shstrtab_name = bss_name + self.bss_name.len() as u32 + 1;
+        let comment_name = shstrtab_name + ".shstrtab".len() as u32 + 1;
+        let rel_text_name = comment_name + ".comment".len() as u32 + 1;
+        let shstrndx = 3 + decoy as u16;
+        let shnum = shstrndx + 1 + !self.comment.is_empty() as u16 + self.reloc_section as u16;
+
+        let shstrtab_offset = self.extra_segment_offset(self.load_segments);
+        let reloc_offset = shstrtab_offset + shstrtab.len();
+        let reloc_size = if self.reloc_section { 8 } else { 0 };
+        let shoff = (reloc_offset + reloc_size).next_multiple_of(4);
+
+        let mut out = Vec::new();
+
+        // ELF header
+        out.extend_from_slice(b"\x7FELF\x01\x01\x01");
+        out.resize(16, 0);
+        put_u16(&mut out, ELF_TYPE_PRX);
+        put_u16(&mut out, self.machine);
+        put_u32(&mut out, 1); // e_version
+        put_u32(&mut out, 0); // e_entry
+        put_u32(&mut out, ELF_HEADER_SIZE as u32); // e_phoff
+        put_u32(&mut out, if self.sections { shoff as u32 } else { 0 });
+        put_u32(&mut out, 0); // e_flags
+        put_u16(&mut out, ELF_HEADER_SIZE as u16);
+        put_u16(&mut out, PHDR_SIZE as u16);
+        put_u16(&mut out, self.load_segments + self.reloc_segments); // e_phnum
+        put_u16(&mut out, SHDR_SIZE as u16);
+        put_u16(&mut out, if self.sections { shnum } else { 0 }); // e_shnum
+        put_u16(&mut out, if self.sections { self.shstrndx.unwrap_or(shstrndx) } else { 0 });
+
+        // PT_LOAD program header, `p_paddr` is the offset of the module info
+        let kernel_bit = if self.kernel { 0x80000000 } else { 0 };
+        put_u32s(&mut out, &[
+            PT_LOAD,
+            segment_offset as u32,
+            0,
+            segment_offset as u32 | kernel_bit,
+            segment_size as u32,
+            segment_size as u32 + self.bss_size,
+            7,
+            self.align,
+        ]);
+        for i in 1..self.load_segments {
+            let (offset, addr) = (self.extra_segment_offset(i) as u32, 0x10000 * i as u32);
+            let size = EXTRA_SEGMENT_SIZE as u32;
+            put_u32s(&mut out, &[PT_LOAD, offset, addr, addr, size, size, 6, 16]);
+        }
+        for _ in 0..self.reloc_segments {
+            let (offset, size) = if self.reloc_section {
+                (reloc_offset as u32, reloc_size as u32)
+            } else {
+                (shstrtab_offset as u32, 0)
+            };
+            put_u32s(&mut out, &[PT_PRX_RELOC2, offset, 0, 0, size, 0, 0, 4]);
+        }
+        out.extend_from_slice(self.comment.as_bytes());
+        out.resize(segment_offset, 0);
+
+        // SceModuleInfo
+        put_u16(&mut out, if self.kernel { 0x1000 } else { 0 });
+        out.push(self.version.1);
+        out.push(self.version.0);
+        let mut name = [0u8; 28];
+        name[..self.name.len()].copy_from_slice(self.name.as_bytes());
+        out.extend_from_slice(&name);
+        // gp_value, ent_top, ent_end, stub_top, stub_end
+        if self.library_tables {
+            put_u32s(&mut out, &[0, 52, 68, 68, 88]);
+        } else {
+            put_u32s(&mut out, &[0; 5]);
+        }
+
+        // The rest of the segment, compressible but not trivially, or random
+        if self.random_payload {
+            let mut state = 0x2545F4914F6CDD1Du64;
+            out.extend((0..self.payload_len).map(|_| {
+                // xorshift64
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 32) as u8
+            }));
+        } else {
+            out.extend((0..self.payload_len).map(|i| (i * 7) as u8));
+        }
+
+        // The segment is at virtual address 0, the tables right after the `SceModuleInfo`
+        if self.library_tables {
+            let mut tables = Vec::new();
+            // Export of the system library: no name, 1 function, 1 variable, NIDs at 88
+            put_u32s(&mut tables, &[0, 0x80000000, 0x00010104, 88]);
+            // Import of `sceTest`: name at 112, 2 functions, NIDs at 104
+            put_u32s(&mut tables, &[112, 0x00090011, 0x00020005, 104, 0]);
+            put_u32s(&mut tables, &[0xD632ACDB, 0xF01D73A7, 0, 0]);
+            put_u32s(&mut tables, &[0x11111111, 0x22222222]);
+            tables.extend_from_slice(b"sceTest\0");
+
+            let start = segment_offset + MOD_INFO_SIZE;
+            out[start..start + tables.len()].copy_from_slice(&tables);
+        }
+
+        for i in 1..self.load_segments {
+            out.extend((0..EXTRA_SEGMENT_SIZE).map(|j| (j as u8) ^ (i as u8)));
+        }
+
+        if !self.sections {
+            return out;
+        }
+
+        out.extend_from_slice(&shstrtab);
+        out.extend(1..=reloc_size as u8);
+        out.resize(shoff, 0);
+
+        // Section headers: null, module info, decoy BSS, BSS, section names, comment
+        out.resize(shoff + SHDR_SIZE, 0);
+        put_shdr(&mut out, mod_info_name, SHT_PROGBITS, 2, 0, segment_offset, MOD_INFO_SIZE);
+        if decoy {
+            let addr = segment_size as u32;
+            put_shdr(&mut out, decoy_bss_name, SHT_NOBITS, 3, addr, shstrtab_offset, 64);
+        }
+        put_shdr(
+            &mut out,
+            bss_name,
+            SHT_NOBITS,
+            3,
+            segment_size as u32,
+            shstrtab_offset,
+            self.bss_size as usize,
+        );
+        put_shdr(&mut out, shstrtab_name, SHT_STRTAB, 0, 0, shstrtab_offset, shstrtab.len());
+        if !self.comment.is_empty() {
+            let (offset, size) = (self.comment_offset(), self.comment.len());
+            put_shdr(&mut out, comment_name, SHT_PROGBITS, 0, 0, offset, size);
+        }
+        if self.reloc_section {
+            put_shdr(&mut out, rel_text_name, SHT_PRX_RELOC, 0, 0, reloc_offset, reloc_size);
+        }
+
+        out
+    }
+}
+
+/// A PARAM.SFO without any entry.
+pub fn empty_sfo() -> Vec<u8> {
+    let mut out = b"\0PSF".to_vec();
+    // version, key table offset, data table offset, number of entries
+    put_u32s(&mut out, &[0x0101, 20, 20, 0]);
+    out
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32s(out: &mut Vec<u8>, values: &[u32]) {
+    for &value in values {
+        put_u32(out, value);
+    }
+}
+
+fn put_shdr(
+    out: &mut Vec<u8>, name: u32, kind: u32, flags: u32, addr: u32, offset: usize, size: usize,
+) {
+    put_u32s(out, &[name, kind, flags, addr, offset as u32, size as u32, 0, 0, 4, 0]);
+}
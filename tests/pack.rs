@@ -0,0 +1,821 @@
+mod fixtures;
+
+use std::io::Cursor;
+
+use fixtures::Prx;
+use psp_packer::{
+    BssSource, DecryptMode, Error, ExecutableKind, ModInfoAttribute, PackOptions, PbpBuilder,
+    PspHeader, UnkPspExecutable,
+};
+
+fn pack(prx: &Prx, options: &PackOptions) -> Result<Vec<u8>, Error> {
+    let exec = UnkPspExecutable::from_reader(prx.build().as_slice())?;
+    Ok(exec.compress_with(options)?.as_bytes().to_vec())
+}
+
+#[test]
+fn user_prx_header() {
+    let prx = Prx::user().name("hello").version(3, 4);
+    let packed = pack(&prx, &PackOptions::new()).unwrap();
+    let header = PspHeader::from_packed(&packed).unwrap();
+
+    assert_eq!(header.signature, 0x5053507E);
+    assert_eq!(header.name(), "hello");
+    assert_eq!((header.module_version_high, header.module_version_low), (3, 4));
+    assert_eq!(header.attribute, ModInfoAttribute::empty());
+    assert_eq!(header.elf_size as usize, prx.build().len());
+    assert_eq!(header.psp_size as usize, packed.len());
+    assert_eq!(header.comp_size as usize, packed.len() - size_of::<PspHeader>());
+    assert_eq!(header.module_info_offset as usize, prx.segment_offset());
+    assert_eq!(header.bss_size, 256);
+    assert_eq!(header.num_segments, 1);
+    assert_eq!(header.seg_align[0], 16);
+    assert_eq!(header.seg_size[0] as usize, prx.segment_size() + 256);
+    assert!(matches!(header.decryption(), Ok(DecryptMode::Standard)));
+    assert_eq!((header.tag, header.oe_tag), ExecutableKind::UserPrx.default_tags());
+}
+
+#[test]
+fn kernel_prx_header() {
+    let packed = pack(&Prx::kernel(), &PackOptions::new()).unwrap();
+    let header = PspHeader::from_packed(&packed).unwrap();
+
+    assert!(header.attribute.contains(ModInfoAttribute::KernelMode));
+    assert!(matches!(header.decryption(), Ok(DecryptMode::Kernel)));
+    assert_eq!((header.tag, header.oe_tag), ExecutableKind::KernelPrx.default_tags());
+}
+
+#[test]
+fn unpack_roundtrip() {
+    let prx = Prx::user();
+    let packed = pack(&prx, &PackOptions::new()).unwrap();
+    let unpacked = UnkPspExecutable::from_reader(packed.as_slice()).unwrap().unpack().unwrap();
+
+    assert_eq!(unpacked.as_bytes(), prx.build());
+}
+
+#[test]
+fn unpack_gzip_trailer() {
+    let packed = pack(&Prx::user(), &PackOptions::new()).unwrap();
+    let unpack = |packed: &[u8]| UnkPspExecutable::from_slice(packed).unwrap().unpack();
+    let len = packed.len();
+
+    let mut bad_crc = packed.clone();
+    bad_crc[len - 8] ^= 1;
+    assert!(matches!(
+        unpack(&bad_crc),
+        Err(Error::Corrupted("gzip CRC32 does not match the ELF"))
+    ));
+
+    let mut bad_isize = packed.clone();
+    bad_isize[len - 4] ^= 1;
+    assert!(matches!(
+        unpack(&bad_isize),
+        Err(Error::Corrupted("gzip ISIZE does not match the ELF size"))
+    ));
+
+    // A PSP header with a larger ELF size than the gzip stream, with a matching trailer
+    let mut bad_elf_size = packed.clone();
+    let elf_size = u32::from_le_bytes(bad_elf_size[len - 4..].try_into().unwrap()) + 16;
+    let elf_size_offset = std::mem::offset_of!(PspHeader, elf_size);
+    bad_elf_size[elf_size_offset..elf_size_offset + 4].copy_from_slice(&elf_size.to_le_bytes());
+    bad_elf_size[len - 4..].copy_from_slice(&elf_size.to_le_bytes());
+    assert!(matches!(
+        unpack(&bad_elf_size),
+        Err(Error::Corrupted("gzip stream is shorter than the ELF size"))
+    ));
+}
+
+#[test]
+fn incompressible() {
+    let prx = Prx::user().random_payload(true).payload_len(1 << 20).build();
+    let exec = UnkPspExecutable::from_slice(&prx).unwrap();
+
+    // Unlike `compress`, `compress_to` keeps the payload gzip compressed even if it grows
+    let mut cursor = Cursor::new(Vec::new());
+    exec.compress_to(&mut cursor, &PackOptions::new()).unwrap();
+    let header = PspHeader::from_packed(cursor.get_ref()).unwrap();
+
+    // zlib `deflateBound` with a gzip wrapper
+    let len = prx.len();
+    let bound = len + (len >> 12) + (len >> 14) + (len >> 25) + 13 - 6 + 18;
+    assert!(header.comp_size as usize > len);
+    assert!(header.comp_size as usize <= bound);
+
+    // `compress` falls back to storing it
+    let packed = UnkPspExecutable::from_slice(&prx).unwrap().compress().unwrap();
+    assert_eq!(PspHeader::from_packed(packed.as_bytes()).unwrap().comp_size as usize, len);
+}
+
+#[test]
+fn incompressible_pbp() {
+    let prx = Prx::user().random_payload(true).payload_len(1 << 20).build();
+    let psar = vec![0x5A; 64 * 1024];
+    let pbp = PbpBuilder::new(&fixtures::empty_sfo(), &prx)
+        .icon0(b"ICON0")
+        .psar(&psar)
+        .build()
+        .unwrap();
+
+    // Stored, with the sub-files around the PRX copied as is
+    let packed = UnkPspExecutable::from_slice(&pbp).unwrap().compress().unwrap();
+    let exec = UnkPspExecutable::from_slice(packed.as_bytes()).unwrap();
+    let sections = exec.pbp_sections().unwrap().unwrap();
+    assert_eq!(sections.iter().find(|s| s.name() == "ICON0.PNG").unwrap().data(), b"ICON0");
+    assert_eq!(sections.iter().find(|s| s.name() == "DATA.PSAR").unwrap().data(), psar);
+    assert_eq!(packed.size(), pbp.len() + size_of::<PspHeader>());
+
+    let header = PspHeader::from_header_bytes(packed.header_bytes()).unwrap();
+    assert_eq!(header.comp_size as usize, prx.len());
+}
+
+#[test]
+fn from_bytes() {
+    let prx = Prx::user().build();
+    let packed = pack(&Prx::user(), &PackOptions::new().seed(0)).unwrap();
+
+    let exec = UnkPspExecutable::from_slice(&prx).unwrap();
+    assert_eq!(exec.compress_with(&PackOptions::new().seed(0)).unwrap().as_bytes(), packed);
+
+    let exec = UnkPspExecutable::from_bytes(prx).unwrap();
+    assert_eq!(exec.compress_with(&PackOptions::new().seed(0)).unwrap().as_bytes(), packed);
+}
+
+#[test]
+fn compress_to() {
+    let prx = Prx::user();
+    let exec = UnkPspExecutable::from_slice(&prx.build()).unwrap();
+
+    // Written at the current position of the writer, not at its start
+    let mut cursor = Cursor::new(vec![0xAA; 16]);
+    cursor.set_position(16);
+    let size = exec.compress_to(&mut cursor, &PackOptions::new()).unwrap();
+
+    let written = cursor.into_inner();
+    assert_eq!(written[..16], [0xAA; 16]);
+    assert_eq!(written.len() as u64, 16 + size);
+
+    let packed = &written[16..];
+    assert_eq!(PspHeader::from_packed(packed).unwrap().psp_size as u64, size);
+    let unpacked = UnkPspExecutable::from_slice(packed).unwrap().unpack().unwrap();
+    assert_eq!(unpacked.as_bytes(), prx.build());
+}
+
+#[test]
+fn header_bytes() {
+    let exec = UnkPspExecutable::from_slice(&Prx::user().build()).unwrap();
+    let packed = exec.compress().unwrap();
+    assert_eq!(packed.header_bytes(), &packed.as_bytes()[..size_of::<PspHeader>()]);
+
+    let prx = Prx::user().build();
+    let pbp = PbpBuilder::new(&fixtures::empty_sfo(), &prx).build().unwrap();
+    let packed = UnkPspExecutable::from_bytes(pbp).unwrap().compress().unwrap();
+    assert_eq!(packed.header_bytes().len(), size_of::<PspHeader>());
+    assert!(packed.header_bytes().starts_with(b"~PSP"));
+}
+
+#[test]
+fn header() {
+    let options = PackOptions::new().name("renamed").pad_to(64);
+    let packed = UnkPspExecutable::from_slice(&Prx::user().build()).unwrap();
+    let packed = packed.compress_with(&options).unwrap();
+
+    let header = packed.header();
+    let written = PspHeader::from_header_bytes(packed.header_bytes()).unwrap();
+    assert_eq!(header.key_data0, written.key_data0);
+    assert_eq!(header.comp_size, written.comp_size);
+    assert_eq!(header.name(), "renamed");
+    assert_eq!(header.psp_size as usize, packed.size());
+    assert_eq!((header.tag, header.oe_tag), packed.tags());
+}
+
+#[test]
+fn invalid_decrypt_mode() {
+    let mut packed = pack(&Prx::user(), &PackOptions::new()).unwrap();
+    packed[std::mem::offset_of!(PspHeader, decrypt_mode)] = 0x5;
+
+    // Reading the raw header is fine, only the typed mode fails
+    let header = PspHeader::from_header_bytes(&packed).unwrap();
+    assert_eq!(header.decrypt_mode, 0x5);
+    assert!(matches!(header.decryption(), Err(Error::Corrupted(_))));
+    assert!(header.to_string().contains("Decrypt mode:    unknown (0x5)"));
+
+    assert!(matches!(PspHeader::from_packed(&packed), Err(Error::Corrupted(_))));
+    let exec = UnkPspExecutable::from_slice(&packed).unwrap();
+    assert!(matches!(exec.unpack(), Err(Error::Corrupted(_))));
+    assert!(matches!(exec.probe(), Err(Error::Corrupted(_))));
+}
+
+#[test]
+fn key_data() {
+    let first = pack(&Prx::user(), &PackOptions::new()).unwrap();
+    let header = PspHeader::from_header_bytes(&first[..size_of::<PspHeader>()]).unwrap();
+
+    let second = pack(&Prx::user(), &PackOptions::new().key_data(&header)).unwrap();
+    assert_eq!(first, second);
+
+    assert!(matches!(
+        PspHeader::from_header_bytes(&Prx::user().build()),
+        Err(Error::NotPacked)
+    ));
+}
+
+#[test]
+fn scheck() {
+    let packed = pack(&Prx::user(), &PackOptions::new()).unwrap();
+    assert_eq!(PspHeader::from_packed(&packed).unwrap().scheck, [0; 0x58]);
+
+    let scheck = [0xA5; 0x58];
+    let packed = pack(&Prx::user(), &PackOptions::new().scheck(scheck)).unwrap();
+    assert_eq!(PspHeader::from_packed(&packed).unwrap().scheck, scheck);
+}
+
+#[test]
+fn comp_attribute() {
+    let packed = pack(&Prx::user(), &PackOptions::new().comp_attribute(0x5)).unwrap();
+    let header = PspHeader::from_packed(&packed).unwrap();
+    assert_eq!(header.comp_attribute, 0x5);
+    // Still gzip compressed
+    assert!(header.comp_size < header.elf_size);
+
+    let packed = pack(&Prx::user(), &PackOptions::new().level(0).comp_attribute(0x1)).unwrap();
+    let header = PspHeader::from_packed(&packed).unwrap();
+    assert_eq!(header.comp_attribute, 0x1);
+    assert_eq!(header.comp_size, header.elf_size);
+}
+
+#[test]
+fn already_packed() {
+    let packed = pack(&Prx::user(), &PackOptions::new()).unwrap();
+    let exec = UnkPspExecutable::from_reader(packed.as_slice()).unwrap();
+
+    assert!(matches!(exec.compress(), Err(Error::AlreadyPacked)));
+}
+
+#[test]
+fn preserve_reserved() {
+    let mut packed = pack(&Prx::user(), &PackOptions::new()).unwrap();
+    let reserved = std::mem::offset_of!(PspHeader, reserved);
+    packed[reserved..reserved + 4].copy_from_slice(&0xDEADBEEFu32.to_le_bytes());
+    let scheck = std::mem::offset_of!(PspHeader, scheck);
+    packed[scheck] = 0x42;
+
+    let repack = |options: PackOptions| {
+        let exec = UnkPspExecutable::from_slice(&packed).unwrap();
+        let repacked = exec.compress_with(&options.force(true)).unwrap();
+        PspHeader::from_packed(repacked.as_bytes()).unwrap()
+    };
+
+    let header = repack(PackOptions::new());
+    assert_eq!(header.reserved, [0; 5]);
+    assert_eq!(header.scheck[0], 0);
+
+    let header = repack(PackOptions::new().preserve_reserved(true));
+    assert_eq!(header.reserved, [0xDEADBEEF, 0, 0, 0, 0]);
+    assert_eq!(header.scheck[0], 0x42);
+}
+
+#[test]
+fn forced_kind() {
+    let options = PackOptions::new().kind(ExecutableKind::KernelPrx);
+    let packed = pack(&Prx::user(), &options).unwrap();
+    let header = PspHeader::from_packed(&packed).unwrap();
+
+    assert!(header.attribute.contains(ModInfoAttribute::KernelMode));
+    assert!(matches!(header.decryption(), Ok(DecryptMode::Kernel)));
+    assert_eq!((header.tag, header.oe_tag), ExecutableKind::KernelPrx.default_tags());
+
+    let options = PackOptions::new().kind(ExecutableKind::UserPrx);
+    let packed = pack(&Prx::kernel(), &options).unwrap();
+    let header = PspHeader::from_packed(&packed).unwrap();
+    assert!(!header.attribute.contains(ModInfoAttribute::KernelMode));
+
+    let options = PackOptions::new().kind(ExecutableKind::Pbp);
+    assert!(matches!(pack(&Prx::user(), &options), Err(Error::NotPbp)));
+}
+
+#[test]
+fn machine() {
+    let exec = UnkPspExecutable::from_reader(Prx::user().build().as_slice()).unwrap();
+    assert!(exec.validate_machine().is_ok());
+
+    let exec = UnkPspExecutable::from_reader(Prx::user().machine(62).build().as_slice()).unwrap();
+    assert!(matches!(exec.validate_machine(), Err(Error::NotMips(62))));
+}
+
+#[test]
+fn attribute() {
+    let exec = UnkPspExecutable::from_slice(&Prx::kernel().build()).unwrap();
+    assert!(exec.validate_attribute().is_ok());
+
+    let prx = Prx::user();
+    let mut bytes = prx.build();
+    let mod_attr = prx.segment_offset();
+    bytes[mod_attr..mod_attr + 2].copy_from_slice(&0x0005u16.to_le_bytes());
+
+    let exec = UnkPspExecutable::from_bytes(bytes).unwrap();
+    assert!(matches!(exec.validate_attribute(), Err(Error::UnknownAttribute(0x0005))));
+    assert!(exec.compress().is_ok());
+}
+
+#[test]
+fn segment_align() {
+    let packed = pack(&Prx::user().align(0x8000), &PackOptions::new()).unwrap();
+    assert_eq!(PspHeader::from_packed(&packed).unwrap().seg_align[0], 0x8000);
+
+    let prx = Prx::user().align(0x10000);
+    assert!(matches!(
+        pack(&prx, &PackOptions::new()),
+        Err(Error::SegmentAlign(0x10000))
+    ));
+}
+
+#[test]
+fn reloc_segments() {
+    // Not counted as segments, even past the limit of 4
+    let prx = Prx::user().reloc_segments(5);
+    let packed = pack(&prx, &PackOptions::new()).unwrap();
+    assert_eq!(PspHeader::from_packed(&packed).unwrap().num_segments, 1);
+
+    // And kept in the packed ELF
+    let unpacked = UnkPspExecutable::from_slice(&packed).unwrap().unpack().unwrap();
+    assert_eq!(unpacked.as_bytes(), prx.build());
+}
+
+#[test]
+fn load_segments() {
+    // 5 program headers, of which only the 2 loadable ones are described
+    let prx = Prx::user().load_segments(2).reloc_segments(3);
+    let packed = pack(&prx, &PackOptions::new()).unwrap();
+    let header = PspHeader::from_packed(&packed).unwrap();
+    assert_eq!(header.num_segments, 2);
+    assert_eq!(header.seg_addr[..2], [0, 0x10000]);
+    assert_eq!(header.seg_size[..2], [prx.segment_size() as u32 + 256, 256]);
+    assert_eq!(header.seg_align[..2], [16, 16]);
+    assert_eq!(header.seg_addr[2..], [0, 0]);
+
+    let unpacked = UnkPspExecutable::from_slice(&packed).unwrap().unpack().unwrap();
+    assert_eq!(unpacked.as_bytes(), prx.build());
+}
+
+#[test]
+fn segment_count() {
+    // No program headers at all
+    let mut prx = Prx::user().build();
+    prx[44..46].copy_from_slice(&0u16.to_le_bytes());
+    let exec = UnkPspExecutable::from_bytes(prx).unwrap();
+    assert!(matches!(exec.compress(), Err(Error::NoSegments)));
+
+    // 5 `PT_LOAD` program headers, turning the relocation ones into loadable ones
+    let mut prx = Prx::user().reloc_segments(4).build();
+    for i in 1..5 {
+        let p_type = 52 + 32 * i;
+        prx[p_type..p_type + 4].copy_from_slice(&1u32.to_le_bytes());
+    }
+    let exec = UnkPspExecutable::from_bytes(prx).unwrap();
+    assert!(matches!(exec.compress(), Err(Error::TooManySegments(5))));
+}
+
+#[test]
+fn program_headers() {
+    let prx = Prx::kernel().reloc_segments(1);
+    let exec = UnkPspExecutable::from_slice(&prx.build()).unwrap();
+    let phdrs = exec.program_headers().unwrap();
+
+    assert_eq!(phdrs.len(), 2);
+    assert_eq!(phdrs[0].type_name(), Some("LOAD"));
+    assert_eq!(phdrs[0].p_paddr as usize, 0x80000000 | prx.segment_offset());
+    assert_eq!(phdrs[1].type_name(), Some("PRX_RELOC2"));
+
+    let packed = pack(&prx, &PackOptions::new()).unwrap();
+    let exec = UnkPspExecutable::from_slice(&packed).unwrap();
+    assert!(matches!(exec.program_headers(), Err(Error::AlreadyPacked)));
+}
+
+#[test]
+fn section_headers() {
+    let names = |prx: Prx| {
+        let exec = UnkPspExecutable::from_bytes(prx.build()).unwrap();
+        let sections = exec.section_headers().unwrap();
+        sections
+            .iter()
+            .map(|section| section.name().map(|name| name.to_str().unwrap().to_owned()))
+            .collect::<Vec<_>>()
+    };
+
+    let exec = UnkPspExecutable::from_bytes(Prx::user().bss_size(0x40).build()).unwrap();
+    let sections = exec.section_headers().unwrap();
+    assert_eq!(sections.len(), 4);
+    assert_eq!(sections[2].name(), Some(c".bss"));
+    assert_eq!(sections[2].header().type_name(), Some("NOBITS"));
+    assert_eq!(sections[2].header().sh_size, 0x40);
+    assert_eq!(names(Prx::user()), [
+        Some(String::new()),
+        Some(".rodata.sceModuleInfo".into()),
+        Some(".bss".into()),
+        Some(".shstrtab".into())
+    ]);
+
+    // Without a string table, or with an invalid one, only the names are missing
+    assert_eq!(names(Prx::user().shstrndx(0)), [None, None, None, None]);
+    assert_eq!(names(Prx::user().shstrndx(9)), [None, None, None, None]);
+    assert!(names(Prx::user().sections(false)).is_empty());
+
+    let packed = pack(&Prx::user(), &PackOptions::new()).unwrap();
+    let exec = UnkPspExecutable::from_slice(&packed).unwrap();
+    assert!(matches!(exec.section_headers(), Err(Error::AlreadyPacked)));
+}
+
+#[test]
+fn symbols() {
+    let exec = UnkPspExecutable::from_bytes(Prx::user().build()).unwrap();
+    let symbols = exec.symbols().unwrap();
+    assert!(symbols.exports().is_empty());
+    assert!(symbols.imports().is_empty());
+
+    let prx = Prx::user().library_tables(true);
+    let exec = UnkPspExecutable::from_bytes(prx.build()).unwrap();
+    let symbols = exec.symbols().unwrap();
+    let [export] = symbols.exports() else {
+        panic!("expected one export")
+    };
+    assert_eq!(export.name(), None);
+    assert_eq!(export.attribute(), 0x8000);
+    assert_eq!(export.functions(), [0xD632ACDB]);
+    assert_eq!(export.variables(), [0xF01D73A7]);
+    let [import] = symbols.imports() else {
+        panic!("expected one import")
+    };
+    assert_eq!(import.name(), Some("sceTest"));
+    assert_eq!(import.version(), 0x0011);
+    assert_eq!(import.attribute(), 0x0009);
+    assert_eq!(import.functions(), [0x11111111, 0x22222222]);
+    assert!(import.variables().is_empty());
+
+    // A packed module has to be unpacked first
+    let packed = pack(&prx, &PackOptions::new()).unwrap();
+    let exec = UnkPspExecutable::from_slice(&packed).unwrap();
+    assert!(matches!(exec.symbols(), Err(Error::AlreadyPacked)));
+    let unpacked = exec.unpack().unwrap();
+    assert_eq!(unpacked.symbols().unwrap().imports().len(), 1);
+
+    // NIDs out of the segment
+    let mut bad = prx.build();
+    let nid_table = prx.segment_offset() + 52 + 16 + 12;
+    bad[nid_table..nid_table + 4].copy_from_slice(&0x10000u32.to_le_bytes());
+    let exec = UnkPspExecutable::from_bytes(bad).unwrap();
+    assert!(matches!(exec.symbols(), Err(Error::BadLibraryTable(68))));
+
+    // A table of no size
+    let mut bad = prx.build();
+    bad[prx.segment_offset() + 52 + 8] = 0;
+    let exec = UnkPspExecutable::from_bytes(bad).unwrap();
+    assert!(matches!(exec.symbols(), Err(Error::BadLibraryTable(52))));
+}
+
+#[test]
+fn headers() {
+    let prx = Prx::user().build();
+    let exec = UnkPspExecutable::from_slice(&prx).unwrap();
+    let elf_header = exec.elf_header().unwrap();
+    assert_eq!(elf_header.e_type, 0xFFA0);
+    assert_eq!(elf_header.e_phnum, 1);
+    let mod_info = exec.module_info().unwrap();
+    assert_eq!(mod_info.mod_attr, ModInfoAttribute::empty());
+    assert_eq!((mod_info.mod_version_high, mod_info.mod_version_low), (1, 2));
+    assert!(mod_info.mod_name.starts_with(b"test_module\0"));
+    assert!(exec.pbp_header().unwrap().is_none());
+
+    // Inside a PBP, the PRX is found through the PBP header
+    let pbp = PbpBuilder::new(&fixtures::empty_sfo(), &prx).build().unwrap();
+    let exec = UnkPspExecutable::from_bytes(pbp).unwrap();
+    let pbp_header = exec.pbp_header().unwrap().unwrap();
+    assert_eq!(pbp_header.magic, 0x50425000);
+    assert_eq!(exec.as_bytes()[pbp_header.prx_offset as usize..], prx);
+    assert_eq!(exec.elf_header().unwrap().e_phnum, 1);
+    assert_eq!(exec.module_info().unwrap().mod_version_low, 2);
+
+    // A packed executable has to be unpacked first
+    let packed = UnkPspExecutable::from_bytes(prx).unwrap().compress().unwrap();
+    let exec = UnkPspExecutable::from_slice(packed.as_bytes()).unwrap();
+    assert!(matches!(exec.elf_header(), Err(Error::AlreadyPacked)));
+    assert!(matches!(exec.module_info(), Err(Error::AlreadyPacked)));
+}
+
+#[test]
+fn strip() {
+    let prx = Prx::user().comment("GCC: (GNU) 9.3.0");
+    let elf = prx.build();
+    let packed = pack(&prx, &PackOptions::new().strip(true)).unwrap();
+    let exec = UnkPspExecutable::from_slice(&packed).unwrap();
+    let unpacked = exec.unpack().unwrap();
+    let stripped = unpacked.as_bytes();
+
+    // Everything after the segment is gone, the comment before it zeroed
+    let segment_end = prx.segment_offset() + prx.segment_size();
+    assert_eq!(stripped.len(), segment_end);
+    assert_eq!(PspHeader::from_packed(&packed).unwrap().elf_size as usize, segment_end);
+    let comment = prx.comment_offset()..prx.comment_offset() + 17;
+    assert!(stripped[comment.clone()].iter().all(|&byte| byte == 0));
+    assert_eq!(stripped[comment.end..], elf[comment.end..segment_end]);
+    assert!(unpacked.section_headers().unwrap().is_empty());
+
+    // The stripped ELF still packs to the same module
+    let repacked = unpacked.compress().unwrap();
+    let header = repacked.header();
+    assert_eq!(header.name(), "test_module");
+    assert_eq!(header.module_info_offset as usize, prx.segment_offset());
+    assert_eq!(header.bss_size, 256);
+
+    let exec = UnkPspExecutable::from_bytes(elf.clone()).unwrap();
+    let stats = exec.compress_with(&PackOptions::new().strip(true)).unwrap().stats();
+    assert_eq!(stats.stripped_size, Some(elf.len() - segment_end));
+    let exec = UnkPspExecutable::from_bytes(elf).unwrap();
+    assert_eq!(exec.compress().unwrap().stats().stripped_size, None);
+}
+
+#[test]
+fn strip_uncovered_relocs() {
+    // The relocations would only be found through the dropped section header table
+    let prx = Prx::user().reloc_section(true);
+    let offset = prx.segment_offset() + prx.segment_size();
+    assert!(matches!(
+        pack(&prx, &PackOptions::new().strip(true)),
+        Err(Error::UncoveredRelocs(o)) if o as usize > offset
+    ));
+    assert_eq!(Error::UncoveredRelocs(0).error_code(), 137);
+    assert!(pack(&prx, &PackOptions::new()).is_ok());
+
+    // Covered by a relocation segment, they are kept
+    let prx = prx.reloc_segments(1);
+    let elf = prx.build();
+    let packed = pack(&prx, &PackOptions::new().strip(true)).unwrap();
+    let unpacked = UnkPspExecutable::from_slice(&packed).unwrap().unpack().unwrap();
+    let stripped = unpacked.as_bytes();
+    assert_eq!(stripped[stripped.len() - 8..], [1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(stripped[prx.segment_offset()..], elf[prx.segment_offset()..stripped.len()]);
+}
+
+#[test]
+fn elf_type() {
+    let with_type = |e_type: u16| {
+        let mut prx = Prx::user().build();
+        prx[16..18].copy_from_slice(&e_type.to_le_bytes());
+        UnkPspExecutable::from_bytes(prx).unwrap()
+    };
+
+    assert!(matches!(with_type(1).compress(), Err(Error::WrongElfType(1))));
+    assert!(matches!(with_type(2).compress(), Err(Error::WrongElfType(2))));
+    assert!(matches!(with_type(3).compress(), Err(Error::NotPrx)));
+    assert!(matches!(
+        psp_packer::detect_kind(with_type(2).as_bytes()),
+        Err(Error::WrongElfType(2))
+    ));
+    assert!(Error::WrongElfType(2).to_string().contains("psp-prxgen"));
+}
+
+#[test]
+fn elf_class() {
+    let mut prx = Prx::user().build();
+    prx[4] = 2; // ELFCLASS64
+    let exec = UnkPspExecutable::from_bytes(prx).unwrap();
+
+    assert!(matches!(psp_packer::detect_kind(exec.as_bytes()), Err(Error::Not32Bit)));
+    assert!(matches!(exec.compress(), Err(Error::Not32Bit)));
+    assert_eq!(Error::Not32Bit.error_code(), 121);
+}
+
+#[test]
+fn elf_data() {
+    let mut prx = Prx::user().build();
+    prx[5] = 2; // ELFDATA2MSB
+    let exec = UnkPspExecutable::from_bytes(prx).unwrap();
+
+    assert!(matches!(
+        psp_packer::detect_kind(exec.as_bytes()),
+        Err(Error::NotLittleEndian(2))
+    ));
+    assert!(matches!(exec.compress(), Err(Error::NotLittleEndian(2))));
+    assert_eq!(Error::NotLittleEndian(2).error_code(), 122);
+}
+
+#[test]
+fn error_variants() {
+    let variants = Error::variants();
+    let codes: Vec<_> = variants.iter().map(Error::error_code).collect();
+    assert_eq!(codes, (101..101 + variants.len() as i32).collect::<Vec<_>>());
+
+    let mut names: Vec<_> = variants.iter().map(Error::name).collect();
+    names.sort_unstable();
+    names.dedup();
+    assert_eq!(names.len(), variants.len());
+}
+
+#[test]
+fn file_size() {
+    use psp_packer::validate_size;
+
+    assert!(matches!(validate_size(0), Err(Error::EmptyFile)));
+    assert!(validate_size(1).is_ok());
+    assert!(validate_size(isize::MAX as usize).is_ok());
+    assert!(matches!(validate_size(isize::MAX as usize + 1), Err(Error::FileTooBig)));
+    assert!(matches!(validate_size(usize::MAX), Err(Error::FileTooBig)));
+
+    assert!(matches!(UnkPspExecutable::from_bytes(Vec::new()), Err(Error::EmptyFile)));
+    assert!(matches!(UnkPspExecutable::from_reader(&[][..]), Err(Error::EmptyFile)));
+}
+
+#[test]
+fn overlap() {
+    let packed = pack(&Prx::user(), &PackOptions::new()).unwrap();
+    assert_eq!(PspHeader::from_packed(&packed).unwrap().overlap_size, 0);
+
+    let packed = pack(&Prx::user(), &PackOptions::new().overlap(true)).unwrap();
+    let header = PspHeader::from_packed(&packed).unwrap();
+    assert!(header.overlap_size > 0);
+    assert!(u32::from(header.overlap_size) < header.comp_size);
+}
+
+#[test]
+fn pad_to() {
+    let unpadded = pack(&Prx::user(), &PackOptions::new().seed(0)).unwrap();
+    let packed = pack(&Prx::user(), &PackOptions::new().seed(0).pad_to(2048)).unwrap();
+    let header = PspHeader::from_packed(&packed).unwrap();
+
+    assert_eq!(packed.len(), unpadded.len().next_multiple_of(2048));
+    assert_eq!(header.psp_size as usize, packed.len());
+    assert_eq!(header.comp_size as usize, unpadded.len() - size_of::<PspHeader>());
+    assert!(packed[unpadded.len()..].iter().all(|&byte| byte == 0));
+
+    let exec = UnkPspExecutable::from_slice(&packed).unwrap();
+    assert!(exec.verify().is_ok());
+    assert_eq!(exec.unpack().unwrap().as_bytes(), Prx::user().build());
+
+    for align in [0, 3, 48] {
+        let options = PackOptions::new().pad_to(align);
+        assert!(matches!(pack(&Prx::user(), &options), Err(Error::PadAlign(a)) if a == align));
+    }
+}
+
+#[test]
+fn self_check() {
+    let options = PackOptions::new().self_check(true).name("renamed");
+    assert!(pack(&Prx::user(), &options).is_ok());
+    assert!(pack(&Prx::kernel(), &options).is_ok());
+}
+
+#[test]
+fn shstrndx_out_of_range() {
+    let prx = Prx::user().shstrndx(4);
+    assert!(matches!(pack(&prx, &PackOptions::new()), Err(Error::NotElf)));
+}
+
+#[test]
+fn absurd_offsets() {
+    let prx = Prx::user().payload_len(64).build();
+
+    // Every header field set to a huge value, one at a time, must fail or pack but not panic
+    for offset in (0..prx.len() - 4).step_by(2) {
+        for value in [0x7FFF_FFFF, 0xFFFF_FFF0, u32::MAX] {
+            let mut prx = prx.clone();
+            prx[offset..offset + 4].copy_from_slice(&u32::to_le_bytes(value));
+
+            let exec = UnkPspExecutable::from_bytes(prx).unwrap();
+            let _ = exec.probe();
+            let _ = exec.compress_with(&PackOptions::new().level(0));
+        }
+    }
+}
+
+#[test]
+fn malformed_pbp() {
+    let prx = Prx::user().build();
+    let mut pbp = PbpBuilder::new(&fixtures::empty_sfo(), &prx).build().unwrap();
+
+    // Swap the DATA.PSP and DATA.PSAR offsets
+    let (prx_offset, psar_offset) = (pbp[0x20..0x24].to_vec(), pbp[0x24..0x28].to_vec());
+    pbp[0x20..0x24].copy_from_slice(&psar_offset);
+    pbp[0x24..0x28].copy_from_slice(&prx_offset);
+
+    let exec = UnkPspExecutable::from_bytes(pbp).unwrap();
+    assert!(matches!(
+        exec.compress(),
+        Err(Error::MalformedPbp {
+            section: "DATA.PSAR",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn pbp_offsets_order() {
+    let prx = Prx::user().build();
+    let mut pbp = PbpBuilder::new(&fixtures::empty_sfo(), &prx).build().unwrap();
+
+    // ICON1.PMF at the end of the file, so PIC0.PNG comes before it
+    let end = pbp.len() as u32;
+    pbp[0x10..0x14].copy_from_slice(&end.to_le_bytes());
+
+    let exec = UnkPspExecutable::from_bytes(pbp).unwrap();
+    let is_malformed = |result| {
+        matches!(
+            result,
+            Err(Error::MalformedPbp {
+                section: "PIC0.PNG",
+                ..
+            })
+        )
+    };
+    assert!(is_malformed(exec.pbp_sections().map(|_| ())));
+    assert!(is_malformed(exec.sfo().map(|_| ())));
+    assert!(is_malformed(exec.probe().map(|_| ())));
+}
+
+#[test]
+fn timings() {
+    let exec = UnkPspExecutable::from_slice(&Prx::user().build()).unwrap();
+    assert!(exec.compress().unwrap().timings().is_none());
+
+    let exec = UnkPspExecutable::from_slice(&Prx::user().build()).unwrap();
+    let packed = exec.compress_with(&PackOptions::new().timings(true)).unwrap();
+    assert!(packed.timings().is_some());
+}
+
+#[test]
+fn no_sections() {
+    let prx = Prx::user().sections(false);
+    let packed = pack(&prx, &PackOptions::new()).unwrap();
+    let header = PspHeader::from_packed(&packed).unwrap();
+
+    // The module info comes from the program header, the BSS from the segment memory size
+    assert_eq!(header.name(), "test_module");
+    assert_eq!(header.module_info_offset as usize, prx.segment_offset());
+    assert_eq!(header.bss_size, 256);
+
+    let unpacked = UnkPspExecutable::from_slice(&packed).unwrap().unpack().unwrap();
+    assert_eq!(unpacked.as_bytes(), prx.build());
+}
+
+#[test]
+fn missing_bss() {
+    // The segments don't need the section
+    let prx = Prx::user().bss_name(".bssx");
+    let packed = pack(&prx, &PackOptions::new()).unwrap();
+    assert_eq!(PspHeader::from_packed(&packed).unwrap().bss_size, 256);
+
+    let options = PackOptions::new().bss_from(BssSource::Section);
+    assert!(matches!(pack(&prx, &options), Err(Error::BssNotFound)));
+
+    let packed = pack(&prx, &options.allow_no_bss(true)).unwrap();
+    assert_eq!(PspHeader::from_packed(&packed).unwrap().bss_size, 0);
+
+    // Without BSS in the segments nor a section, there is none
+    let prx = Prx::user().bss_name(".bssx").bss_size(0);
+    let packed = pack(&prx, &PackOptions::new()).unwrap();
+    assert_eq!(PspHeader::from_packed(&packed).unwrap().bss_size, 0);
+}
+
+#[test]
+fn bss_name_exact() {
+    // `.bssx` comes first, in the section headers and in the section names
+    let prx = Prx::user().decoy_bss(".bssx");
+    let options = PackOptions::new().bss_from(BssSource::Section);
+    assert_eq!(
+        PspHeader::from_packed(&pack(&prx, &options).unwrap()).unwrap().bss_size,
+        256
+    );
+}
+
+#[test]
+fn bss_name_after_longer_name() {
+    // The section names are read up to their own NUL, not the one of a previous longer name
+    let prx = Prx::user().decoy_bss(".bss.decoy");
+    let exec = UnkPspExecutable::from_bytes(prx.build()).unwrap();
+    let names: Vec<_> = exec.section_headers().unwrap().iter().map(|s| s.name()).collect();
+    assert_eq!(names[2..4], [Some(c".bss.decoy"), Some(c".bss")]);
+
+    let options = PackOptions::new().bss_from(BssSource::Section);
+    let packed = pack(&prx, &options).unwrap();
+    assert_eq!(PspHeader::from_packed(&packed).unwrap().bss_size, 256);
+}
+
+#[test]
+fn bss_from() {
+    // The segment memory size and the section size disagree
+    let mut prx = Prx::user().build();
+    let p_memsz = 52 + 20;
+    let memsz = u32::from_le_bytes(prx[p_memsz..p_memsz + 4].try_into().unwrap());
+    prx[p_memsz..p_memsz + 4].copy_from_slice(&(memsz + 0x100).to_le_bytes());
+
+    let bss_size = |prx: &[u8], options: PackOptions| {
+        let exec = UnkPspExecutable::from_slice(prx).unwrap();
+        exec.compress_with(&options).unwrap().header().bss_size
+    };
+    assert_eq!(bss_size(&prx, PackOptions::new()), 0x200);
+    assert_eq!(bss_size(&prx, PackOptions::new().bss_from(BssSource::Section)), 0x100);
+
+    // The section is the fallback when the segments have no BSS
+    let p_filesz = 52 + 16;
+    prx.copy_within(p_memsz..p_memsz + 4, p_filesz);
+    assert_eq!(bss_size(&prx, PackOptions::new()), 0x100);
+}